@@ -0,0 +1,220 @@
+use std::f32::consts::TAU;
+
+use criterion::{black_box, criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+
+use jdsp::{AntiderivativeOrder, NonlinearProcessor, ProcessorState::State, ProcessorStyle};
+use jdsp::{Oversample, OversampleFactor};
+use nih_plug::prelude::*;
+use Nonlinear_ADAA::NonlinearAdaa;
+
+const SAMPLE_RATE: f32 = 44100.0;
+const BLOCK_SIZES: [usize; 4] = [32, 64, 256, 1024];
+const DRIVE: f32 = 10.0;
+
+fn variants<T: Enum>() -> impl Iterator<Item = (T, &'static str)> {
+    T::variants()
+        .iter()
+        .enumerate()
+        .map(|(idx, name)| (T::from_index(idx), *name))
+}
+
+fn sine(num_samples: usize) -> Vec<f32> {
+    (0..num_samples)
+        .map(|n| (TAU * 1000.0 * n as f32 / SAMPLE_RATE).sin())
+        .collect()
+}
+
+fn oversample_times(factor: OversampleFactor) -> usize {
+    2_usize.pow(factor as u32)
+}
+
+fn bench_nonlinear_processor(c: &mut Criterion) {
+    let mut group = c.benchmark_group("nonlinear_processor");
+    let input: Vec<f32> = sine(1024).into_iter().map(|x| x * DRIVE).collect();
+    group.throughput(Throughput::Elements(input.len() as u64));
+
+    for (style, style_name) in variants::<ProcessorStyle>() {
+        for (order, order_name) in variants::<AntiderivativeOrder>() {
+            let mut nl = NonlinearProcessor::new();
+            nl.compare_and_change_state(State(style, order));
+
+            group.bench_function(BenchmarkId::new(style_name, order_name), |b| {
+                b.iter(|| {
+                    for x in input.iter() {
+                        black_box(nl.process(black_box(*x)));
+                    }
+                })
+            });
+        }
+    }
+
+    group.finish();
+}
+
+fn bench_oversample(c: &mut Criterion) {
+    let mut group = c.benchmark_group("oversample");
+
+    for (factor, factor_name) in variants::<OversampleFactor>() {
+        for block_size in BLOCK_SIZES {
+            let mut os = Oversample::new(factor, block_size);
+            os.initialize_oversample_stages();
+
+            let mut input = sine(block_size);
+            let mut up = vec![0.0_f32; block_size * oversample_times(factor)];
+            let mut down = vec![0.0_f32; block_size];
+
+            group.throughput(Throughput::Elements(block_size as u64));
+
+            group.bench_with_input(
+                BenchmarkId::new(format!("process_up/{factor_name}"), block_size),
+                &block_size,
+                |b, _| b.iter(|| os.process_up(black_box(&mut input), &mut up)),
+            );
+
+            group.bench_with_input(
+                BenchmarkId::new(format!("process_down/{factor_name}"), block_size),
+                &block_size,
+                |b, _| b.iter(|| os.process_down(black_box(&mut up), &mut down)),
+            );
+        }
+    }
+
+    group.finish();
+}
+
+/// Stands in for the host during the full plugin benchmark, with a stopped transport and no
+/// events.
+struct BenchContext {
+    transport: Transport,
+}
+
+impl BenchContext {
+    fn new() -> Self {
+        Self {
+            transport: Transport::new(SAMPLE_RATE),
+        }
+    }
+}
+
+impl InitContext<NonlinearAdaa> for BenchContext {
+    fn plugin_api(&self) -> PluginApi {
+        PluginApi::Standalone
+    }
+
+    fn execute(&self, _task: <NonlinearAdaa as Plugin>::BackgroundTask) {}
+
+    fn set_latency_samples(&self, _samples: u32) {}
+
+    fn set_current_voice_capacity(&self, _capacity: u32) {}
+}
+
+impl ProcessContext<NonlinearAdaa> for BenchContext {
+    fn plugin_api(&self) -> PluginApi {
+        PluginApi::Standalone
+    }
+
+    fn execute_background(&self, _task: <NonlinearAdaa as Plugin>::BackgroundTask) {}
+
+    fn execute_gui(&self, _task: <NonlinearAdaa as Plugin>::BackgroundTask) {}
+
+    fn transport(&self) -> &Transport {
+        &self.transport
+    }
+
+    fn next_event(&mut self) -> Option<PluginNoteEvent<NonlinearAdaa>> {
+        None
+    }
+
+    fn send_event(&mut self, _event: PluginNoteEvent<NonlinearAdaa>) {}
+
+    fn set_latency_samples(&self, _samples: u32) {}
+
+    fn set_current_voice_capacity(&self, _capacity: u32) {}
+}
+
+fn set_param(plugin: &NonlinearAdaa, id: &str, plain: f32) {
+    let (_, param, _) = plugin
+        .params()
+        .param_map()
+        .into_iter()
+        .find(|(param_id, _, _)| param_id == id)
+        .unwrap_or_else(|| panic!("unknown parameter id '{id}'"));
+
+    unsafe {
+        param.set_normalized_value(param.preview_normalized(plain));
+        param.update_smoother(SAMPLE_RATE, true);
+    }
+}
+
+fn bench_plugin_process(c: &mut Criterion) {
+    let mut group = c.benchmark_group("plugin_process");
+
+    for (style, style_name) in variants::<ProcessorStyle>() {
+        for (order, order_name) in variants::<AntiderivativeOrder>() {
+            for (factor, factor_name) in variants::<OversampleFactor>() {
+                for block_size in BLOCK_SIZES {
+                    let mut plugin = NonlinearAdaa::default();
+                    let mut context = BenchContext::new();
+                    set_param(&plugin, "nl proc", style.to_index() as f32);
+                    set_param(&plugin, "ad level", order.to_index() as f32);
+                    set_param(&plugin, "os level", factor.to_index() as f32);
+                    set_param(&plugin, "gain", util::db_to_gain(20.0));
+
+                    plugin.initialize(
+                        &NonlinearAdaa::AUDIO_IO_LAYOUTS[0],
+                        &BufferConfig {
+                            sample_rate: SAMPLE_RATE,
+                            min_buffer_size: None,
+                            max_buffer_size: block_size as u32,
+                            process_mode: ProcessMode::Realtime,
+                        },
+                        &mut context,
+                    );
+                    plugin.reset();
+
+                    let input = sine(block_size);
+                    let mut channels = vec![input.clone(); 2];
+                    let mut buffer = Buffer::default();
+                    unsafe {
+                        buffer.set_slices(block_size, |slices| {
+                            slices.clear();
+                            slices.extend(channels.iter_mut().map(|c| c.as_mut_slice()));
+                        });
+                    }
+                    let mut aux = AuxiliaryBuffers {
+                        inputs: &mut [],
+                        outputs: &mut [],
+                    };
+
+                    group.throughput(Throughput::Elements(block_size as u64));
+                    group.bench_with_input(
+                        BenchmarkId::new(
+                            format!("{style_name}/{order_name}/{factor_name}"),
+                            block_size,
+                        ),
+                        &block_size,
+                        |b, _| {
+                            b.iter(|| {
+                                buffer
+                                    .as_slice()
+                                    .iter_mut()
+                                    .for_each(|ch| ch.copy_from_slice(&input));
+                                plugin.process(&mut buffer, &mut aux, &mut context)
+                            })
+                        },
+                    );
+                }
+            }
+        }
+    }
+
+    group.finish();
+}
+
+criterion_group!(
+    benches,
+    bench_nonlinear_processor,
+    bench_oversample,
+    bench_plugin_process
+);
+criterion_main!(benches);
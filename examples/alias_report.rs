@@ -0,0 +1,33 @@
+//! Prints the signal-to-alias ratio of every style, order and oversampling factor combination.
+//!
+//! `cargo run --release --features measure --example alias_report -- [drive]`
+
+use Nonlinear_ADAA::measure::{bin_to_hz, fundamental_bin, measure_sweep, measure_tone};
+use Nonlinear_ADAA::measure::{ChainConfig, TEST_TONES};
+
+fn main() {
+    let drive = std::env::args()
+        .nth(1)
+        .map(|d| d.parse::<f32>().expect("drive must be a number"))
+        .unwrap_or(10.0);
+
+    print!("{:<40}", format!("config (drive x{drive})"));
+    for freq in TEST_TONES {
+        print!(
+            " {:>10}",
+            format!("{:.0} Hz", bin_to_hz(fundamental_bin(freq)))
+        );
+    }
+    println!(" {:>10}", "sweep");
+
+    for (name, config) in ChainConfig::all(drive) {
+        print!("{name:<40}");
+        for freq in TEST_TONES {
+            print!(
+                " {:>10.2}",
+                measure_tone(&config, freq).signal_to_alias_db()
+            );
+        }
+        println!(" {:>10.2}", measure_sweep(&config).signal_to_alias_db());
+    }
+}
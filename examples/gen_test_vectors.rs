@@ -0,0 +1,110 @@
+//! Regenerates or creates the JSON test vectors used by `tests/integration_tests.rs`.
+//!
+//! ```shell
+//! # re-render expected_output of every vector (or only the given files) with the current DSP
+//! cargo run --features measure --example gen_test_vectors -- regen [PATH...]
+//! # render a new vector from deterministic noise
+//! cargo run --features measure --example gen_test_vectors -- new OUT STYLE ORDER FACTOR DRIVE BLOCK_SIZE
+//! ```
+
+use std::path::PathBuf;
+use std::process::ExitCode;
+
+use Nonlinear_ADAA::measure::ChainConfig;
+use Nonlinear_ADAA::test_vectors::{
+    discover, factor_from_times, order_from_number, parse_style, summarize_results, TestVector,
+    VectorConfig, TEST_VECTOR_DIR,
+};
+
+const USAGE: &str = "usage:
+    gen_test_vectors regen [PATH...]
+    gen_test_vectors new OUT STYLE ORDER FACTOR DRIVE BLOCK_SIZE";
+
+fn regen(paths: Vec<PathBuf>) -> Result<(), String> {
+    let paths = if paths.is_empty() {
+        discover(TEST_VECTOR_DIR).map_err(|e| e.to_string())?
+    } else {
+        paths
+    };
+
+    for path in paths {
+        let mut vector = TestVector::read(&path).map_err(|e| format!("{}: {e}", path.display()))?;
+        let config = vector
+            .config()
+            .map_err(|e| format!("{}: {e}", path.display()))?;
+        let output = vector.render(&config);
+
+        let total = summarize_results(&output, &vector.expected_output);
+        if total.num_incorrect_results.is_some() {
+            println!("{}: {:?}", path.display(), total);
+        } else {
+            println!("{}: unchanged", path.display());
+        }
+
+        // write the resolved settings back so the vector no longer relies on its name
+        vector = TestVector::new(vector.name.clone(), config, vector.input);
+        vector
+            .write(&path)
+            .map_err(|e| format!("{}: {e}", path.display()))?;
+    }
+
+    Ok(())
+}
+
+fn new(args: &[String]) -> Result<(), String> {
+    let [out, style, order, factor, drive, block_size] = args else {
+        return Err(USAGE.to_string());
+    };
+
+    let parse_number = |s: &str| s.parse::<u32>().map_err(|e| format!("'{s}': {e}"));
+    let block_size = parse_number(block_size)? as usize;
+
+    let config = VectorConfig {
+        chain: ChainConfig {
+            style: parse_style(style).ok_or_else(|| format!("unknown style '{style}'"))?,
+            order: order_from_number(parse_number(order)?)
+                .ok_or_else(|| format!("unknown order '{order}'"))?,
+            factor: factor_from_times(parse_number(factor)?)
+                .ok_or_else(|| format!("unknown oversample factor '{factor}'"))?,
+            drive: drive.parse().map_err(|e| format!("'{drive}': {e}"))?,
+        },
+        block_size,
+    };
+
+    // deterministic noise with roughly the level of the original vectors
+    let mut state: u32 = 0x9e37_79b9;
+    let input = (0..block_size)
+        .map(|_| {
+            state ^= state << 13;
+            state ^= state >> 17;
+            state ^= state << 5;
+            (state as f32 / u32::MAX as f32 * 2.0 - 1.0) * 2.0
+        })
+        .collect();
+
+    let name = format!("{style} {factor}x adaa level {order} x{drive} gain size {block_size}");
+    TestVector::new(name, config, input)
+        .write(out)
+        .map_err(|e| format!("{out}: {e}"))?;
+
+    println!("wrote {out}");
+    Ok(())
+}
+
+fn main() -> ExitCode {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+
+    let result = match args.first().map(String::as_str) {
+        Some("regen") => regen(args[1..].iter().map(PathBuf::from).collect()),
+        Some("new") => new(&args[1..]),
+        _ => Err(USAGE.to_string()),
+    };
+
+    match result {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("{e}");
+            ExitCode::FAILURE
+        }
+    }
+}
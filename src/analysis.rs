@@ -0,0 +1,270 @@
+//! Live harmonic distortion analysis. The plugin either injects a test tone at its input or
+//! leaves the input alone, and the editor periodically analyses the processed signal the scope
+//! captures.
+
+use nih_plug::prelude::*;
+use nih_plug_vizia::vizia::prelude::Context;
+use rustfft::{num_complex::Complex, FftPlanner};
+use std::f64::consts::{PI, TAU};
+use std::ops::RangeInclusive;
+use std::sync::atomic::{AtomicU8, Ordering};
+use std::sync::{Arc, Weak};
+use std::time::Duration;
+
+use crate::editor;
+use crate::scope::Scope;
+use crate::NonlinearAdaaParams;
+
+const ANALYSIS_INTERVAL: Duration = Duration::from_millis(250);
+
+pub const FFT_SIZE: usize = 16384;
+
+/// Harmonics listed individually by [`harmonic_analysis`].
+pub const ANALYSIS_HARMONICS: RangeInclusive<usize> = 2..=10;
+pub const NUM_ANALYSIS_HARMONICS: usize = 9;
+// below this level relative to full scale there is no tone worth analysing
+const MIN_FUNDAMENTAL_DB: f64 = -80.0;
+// half width of the Blackman-Harris main lobe plus a bin for tones between bins
+const ANALYSIS_BIN_SPREAD: usize = 5;
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum AnalysisMode {
+    Off,
+    /// Analyses whatever is playing, following its strongest component.
+    Detect,
+    /// Replaces the input with a sine at the analysis frequency and level.
+    Inject,
+}
+
+impl AnalysisMode {
+    pub const ALL: [AnalysisMode; 3] = [
+        AnalysisMode::Off,
+        AnalysisMode::Detect,
+        AnalysisMode::Inject,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            AnalysisMode::Off => "Off",
+            AnalysisMode::Detect => "Detect",
+            AnalysisMode::Inject => "Inject",
+        }
+    }
+}
+
+/// The analysis mode picked in the editor. It is neither a parameter nor saved with the plugin
+/// state, so every instance starts with the analysis off and a project can't reload with the test
+/// tone replacing its input.
+#[derive(Debug, Default)]
+pub struct AnalysisModeState(AtomicU8);
+
+impl AnalysisModeState {
+    pub fn get(&self) -> AnalysisMode {
+        AnalysisMode::ALL[self.0.load(Ordering::Relaxed) as usize]
+    }
+
+    pub fn set(&self, mode: AnalysisMode) {
+        self.0.store(mode as u8, Ordering::Relaxed);
+    }
+}
+
+/// Sine generator for the injected tone.
+#[derive(Default)]
+pub struct TestTone {
+    phase: f64,
+}
+
+impl TestTone {
+    #[inline]
+    pub fn next(&mut self, frequency: f32, sample_rate: f32) -> f32 {
+        let sample = self.phase.sin();
+        self.phase = (self.phase + TAU * frequency as f64 / sample_rate as f64) % TAU;
+        sample as f32
+    }
+
+    pub fn reset(&mut self) {
+        self.phase = 0.0;
+    }
+}
+
+/// Analyses the scope's processed signal every [`ANALYSIS_INTERVAL`] and emits the result with
+/// `event`, along with the Nyquist frequency of the oversampled section that harmonics fold back
+/// from.
+pub fn spawn_analyzer<E, F>(
+    cx: &mut Context,
+    scope: Arc<Scope>,
+    params: Arc<NonlinearAdaaParams>,
+    alive: Weak<()>,
+    event: F,
+) where
+    E: Send + 'static,
+    F: Fn(Option<HarmonicAnalysis>, f32) -> E + Send + 'static,
+{
+    let mut signal = vec![0.0; FFT_SIZE];
+    editor::poll_while_open(cx, alive, ANALYSIS_INTERVAL, move || {
+        let sample_rate = scope.sample_rate.load(Ordering::Relaxed);
+        let oversampled_nyquist =
+            sample_rate * scope.oversampling_times.load(Ordering::Relaxed) as f32 / 2.0;
+        let analysis = match params.analysis_mode.get() {
+            AnalysisMode::Off => None,
+            AnalysisMode::Detect => {
+                scope.wet.snapshot(&mut signal);
+                harmonic_analysis(&signal, sample_rate, None)
+            }
+            AnalysisMode::Inject => {
+                scope.wet.snapshot(&mut signal);
+                harmonic_analysis(
+                    &signal,
+                    sample_rate,
+                    Some(params.input.analysis_frequency.value()),
+                )
+            }
+        };
+        Some(event(analysis, oversampled_nyquist))
+    });
+}
+
+/// 4-term Blackman-Harris window. Its side lobes sit 92 dB down, so a tone that falls between
+/// bins does not bury the distortion under leakage the way Hann's side lobes do.
+fn blackman_harris(phase: f64) -> f64 {
+    0.35875 - 0.48829 * phase.cos() + 0.14128 * (2.0 * phase).cos() - 0.01168 * (3.0 * phase).cos()
+}
+
+/// `window` is given the phase `2 pi n / N` of each sample.
+pub(crate) fn windowed_power_spectrum(signal: &[f32], window: impl Fn(f64) -> f64) -> Vec<f64> {
+    assert!(signal.len() >= FFT_SIZE, "signal shorter than FFT_SIZE");
+
+    let mut buf: Vec<Complex<f64>> = signal[..FFT_SIZE]
+        .iter()
+        .enumerate()
+        .map(|(n, x)| {
+            let w = window(2.0 * PI * n as f64 / FFT_SIZE as f64);
+            Complex::new(*x as f64 * w, 0.0)
+        })
+        .collect();
+
+    FftPlanner::new()
+        .plan_fft_forward(FFT_SIZE)
+        .process(&mut buf);
+
+    buf.iter()
+        .take(FFT_SIZE / 2 + 1)
+        .map(|c| c.norm_sqr())
+        .collect()
+}
+
+/// Distortion of a steady tone. Levels are in dB relative to the fundamental.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct HarmonicAnalysis {
+    pub fundamental_hz: f32,
+    /// Total harmonic distortion over [`ANALYSIS_HARMONICS`].
+    pub thd_db: f64,
+    /// Everything but the fundamental and DC.
+    pub thd_n_db: f64,
+    /// Levels of [`ANALYSIS_HARMONICS`] in order, `None` for those above Nyquist.
+    pub harmonics_db: [Option<f64>; NUM_ANALYSIS_HARMONICS],
+    /// Energy off every harmonic: aliases folded back from above the oversampled Nyquist
+    /// frequency, plus noise.
+    pub alias_db: f64,
+}
+
+fn power_to_db(power: f64) -> f64 {
+    10.0 * power.max(f64::MIN_POSITIVE).log10()
+}
+
+/// Analyses the first `FFT_SIZE` samples of `signal`, recorded at `sample_rate`. The tone is
+/// looked for near `fundamental_hz`, or taken to be the strongest component when that is `None`.
+/// Returns `None` if the tone is too quiet to analyse.
+pub fn harmonic_analysis(
+    signal: &[f32],
+    sample_rate: f32,
+    fundamental_hz: Option<f32>,
+) -> Option<HarmonicAnalysis> {
+    const SPREAD: usize = ANALYSIS_BIN_SPREAD;
+
+    let spectrum = windowed_power_spectrum(signal, blackman_harris);
+    let last_bin = spectrum.len() - 1;
+    let hz_per_bin = sample_rate as f64 / FFT_SIZE as f64;
+
+    // the strongest bin within a window, so a tone between bins is still found
+    let peak_in = |centre: usize, spread: usize| {
+        (centre.saturating_sub(spread)..=(centre + spread).min(last_bin))
+            .max_by(|a, b| spectrum[*a].total_cmp(&spectrum[*b]))
+            .unwrap_or(centre)
+    };
+
+    let peak = match fundamental_hz {
+        Some(hz) => peak_in((hz as f64 / hz_per_bin).round() as usize, SPREAD),
+        None => peak_in(last_bin / 2, last_bin / 2 - SPREAD),
+    };
+    if peak <= SPREAD || peak >= last_bin - SPREAD {
+        return None;
+    }
+
+    // parabolic interpolation on the log spectrum places the tone between bins, which keeps the
+    // higher harmonics on target
+    let (a, b, c) = (
+        power_to_db(spectrum[peak - 1]),
+        power_to_db(spectrum[peak]),
+        power_to_db(spectrum[peak + 1]),
+    );
+    let offset = if a - 2.0 * b + c < 0.0 {
+        (0.5 * (a - c) / (a - 2.0 * b + c)).clamp(-0.5, 0.5)
+    } else {
+        0.0
+    };
+    let fundamental_bin = peak as f64 + offset;
+
+    let lobe_power = |centre: usize| -> f64 {
+        (centre - SPREAD..=centre + SPREAD)
+            .map(|bin| spectrum[bin])
+            .sum()
+    };
+    let harmonic_centre = |k: usize| {
+        let centre = (k as f64 * fundamental_bin).round() as usize;
+        (centre + SPREAD + 2 < last_bin).then(|| peak_in(centre, 2))
+    };
+
+    let fundamental = lobe_power(peak);
+    // by Parseval, a full scale sine leaves N/4 times the window's energy in the positive bins
+    let window_energy: f64 = (0..FFT_SIZE)
+        .map(|n| blackman_harris(2.0 * PI * n as f64 / FFT_SIZE as f64).powi(2))
+        .sum();
+    let full_scale = FFT_SIZE as f64 * window_energy / 4.0;
+    if power_to_db(fundamental / full_scale) < MIN_FUNDAMENTAL_DB {
+        return None;
+    }
+
+    let mut harmonics_db = [None; NUM_ANALYSIS_HARMONICS];
+    let mut harmonic_sum = 0.0;
+    for (level, k) in harmonics_db.iter_mut().zip(ANALYSIS_HARMONICS) {
+        if let Some(centre) = harmonic_centre(k) {
+            let power = lobe_power(centre);
+            harmonic_sum += power;
+            *level = Some(power_to_db(power / fundamental));
+        }
+    }
+
+    // every harmonic up to Nyquist counts as wanted distortion when looking for aliases
+    let mut on_harmonic = vec![false; spectrum.len()];
+    for centre in (1..).map_while(harmonic_centre) {
+        on_harmonic[centre - SPREAD..=centre + SPREAD].fill(true);
+    }
+
+    let above_dc: f64 = spectrum.iter().skip(SPREAD + 1).sum();
+    let off_harmonics: f64 = spectrum
+        .iter()
+        .zip(on_harmonic.iter())
+        .skip(SPREAD + 1)
+        .filter(|(_, on)| !**on)
+        .map(|(power, _)| power)
+        .sum();
+
+    Some(HarmonicAnalysis {
+        fundamental_hz: (fundamental_bin * hz_per_bin) as f32,
+        thd_db: power_to_db(harmonic_sum / fundamental),
+        thd_n_db: power_to_db((above_dc - fundamental) / fundamental),
+        harmonics_db,
+        alias_db: power_to_db(off_harmonics / fundamental),
+    })
+}
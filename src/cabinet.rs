@@ -0,0 +1,369 @@
+//! Cabinet impulse response stage.
+//!
+//! Impulse responses are loaded, resampled and transformed on the background thread and handed
+//! to the audio thread through a [`CabinetExchange`]. Convolution is uniformly partitioned
+//! overlap-save with a partition the size of the plugin's processing block, which adds exactly
+//! [`CABINET_LATENCY`] samples of latency whenever the stage is enabled.
+
+use std::f32::consts::PI;
+use std::path::Path;
+use std::sync::{Arc, Mutex, Weak};
+use std::time::Duration;
+
+use nih_plug_vizia::vizia::prelude::Context;
+use rustfft::{num_complex::Complex, Fft, FftPlanner};
+
+use crate::editor;
+use crate::{NonlinearAdaaParams, MAX_BLOCK_SIZE};
+
+const PARTITION_SIZE: usize = MAX_BLOCK_SIZE;
+const FFT_SIZE: usize = PARTITION_SIZE * 2;
+/// Loaded impulse responses are truncated to this length.
+pub const MAX_IR_SECONDS: f32 = 1.0;
+pub const CABINET_LATENCY: usize = PARTITION_SIZE;
+// half width of the windowed sinc used when resampling an impulse response
+const RESAMPLE_TAPS: isize = 16;
+const WATCH_INTERVAL: Duration = Duration::from_millis(100);
+
+pub struct Cabinet {
+    path: Option<String>,
+    sample_rate: f32,
+    // one set of partitioned spectra per IR channel, `num_partitions * FFT_SIZE` long
+    kernels: Vec<Vec<Complex<f32>>>,
+    num_partitions: usize,
+    channels: [ChannelConvolver; 2],
+    fft: Arc<dyn Fft<f32>>,
+    ifft: Arc<dyn Fft<f32>>,
+    fft_buffer: Vec<Complex<f32>>,
+    scratch: Vec<Complex<f32>>,
+}
+
+struct ChannelConvolver {
+    // previous and current partition of input
+    input: [f32; FFT_SIZE],
+    output: [f32; PARTITION_SIZE],
+    fifo_pos: usize,
+    // frequency domain delay line of past input partitions
+    fdl: Vec<Complex<f32>>,
+    fdl_pos: usize,
+}
+
+impl ChannelConvolver {
+    fn new(num_partitions: usize) -> Self {
+        Self {
+            input: [0.0; FFT_SIZE],
+            output: [0.0; PARTITION_SIZE],
+            fifo_pos: 0,
+            fdl: vec![Complex::default(); num_partitions * FFT_SIZE],
+            fdl_pos: 0,
+        }
+    }
+}
+
+impl Cabinet {
+    /// A stage without an impulse response. It still delays the signal by [`CABINET_LATENCY`] so
+    /// the reported latency does not depend on whether an IR is loaded.
+    pub fn empty(sample_rate: f32) -> Self {
+        Self::from_impulse_response(None, sample_rate, Vec::new())
+    }
+
+    /// Reads a mono or stereo WAV file and prepares it for `sample_rate`.
+    pub fn load<P: AsRef<Path>>(path: P, sample_rate: f32) -> Result<Self, String> {
+        let path = path.as_ref();
+        let mut reader = hound::WavReader::open(path).map_err(|e| e.to_string())?;
+        let spec = reader.spec();
+        let num_channels = spec.channels as usize;
+
+        if !(1..=2).contains(&num_channels) {
+            return Err(format!(
+                "{} has {num_channels} channels, only mono and stereo impulse responses are supported",
+                path.display()
+            ));
+        }
+
+        let samples: Vec<f32> = match spec.sample_format {
+            hound::SampleFormat::Float => reader.samples::<f32>().collect::<Result<_, _>>(),
+            hound::SampleFormat::Int => {
+                let scale = 1.0 / (1_i64 << (spec.bits_per_sample - 1)) as f32;
+                reader
+                    .samples::<i32>()
+                    .map(|s| s.map(|s| s as f32 * scale))
+                    .collect::<Result<_, _>>()
+            }
+        }
+        .map_err(|e| e.to_string())?;
+
+        let max_len = (MAX_IR_SECONDS * sample_rate) as usize;
+        let mut channels: Vec<Vec<f32>> = (0..num_channels)
+            .map(|ch| {
+                let channel: Vec<f32> = samples
+                    .iter()
+                    .skip(ch)
+                    .step_by(num_channels)
+                    .copied()
+                    .collect();
+                let mut resampled = resample(&channel, spec.sample_rate as f32, sample_rate);
+                resampled.truncate(max_len);
+                resampled
+            })
+            .collect();
+
+        // normalise to unit energy across channels so different IRs land at similar levels
+        let energy = channels.iter().flatten().map(|x| x * x).sum::<f32>() / num_channels as f32;
+        if energy > 0.0 {
+            let scale = energy.sqrt().recip();
+            channels.iter_mut().flatten().for_each(|x| *x *= scale);
+        }
+
+        Ok(Self::from_impulse_response(
+            Some(path.to_string_lossy().into_owned()),
+            sample_rate,
+            channels,
+        ))
+    }
+
+    fn from_impulse_response(path: Option<String>, sample_rate: f32, ir: Vec<Vec<f32>>) -> Self {
+        let mut planner = FftPlanner::new();
+        let fft = planner.plan_fft_forward(FFT_SIZE);
+        let ifft = planner.plan_fft_inverse(FFT_SIZE);
+        let scratch_len = fft
+            .get_inplace_scratch_len()
+            .max(ifft.get_inplace_scratch_len());
+        let mut scratch = vec![Complex::default(); scratch_len];
+
+        let num_partitions = ir
+            .iter()
+            .map(|channel| channel.len().div_ceil(PARTITION_SIZE))
+            .max()
+            .unwrap_or(0);
+
+        let kernels = ir
+            .iter()
+            .map(|channel| {
+                let mut kernel = vec![Complex::default(); num_partitions * FFT_SIZE];
+                kernel
+                    .chunks_mut(FFT_SIZE)
+                    .zip(channel.chunks(PARTITION_SIZE))
+                    .for_each(|(spectrum, partition)| {
+                        spectrum
+                            .iter_mut()
+                            .zip(partition.iter())
+                            .for_each(|(bin, x)| *bin = Complex::new(*x, 0.0));
+                        fft.process_with_scratch(spectrum, &mut scratch);
+                    });
+                kernel
+            })
+            .collect();
+
+        Self {
+            path,
+            sample_rate,
+            kernels,
+            num_partitions,
+            channels: [
+                ChannelConvolver::new(num_partitions),
+                ChannelConvolver::new(num_partitions),
+            ],
+            fft,
+            ifft,
+            fft_buffer: vec![Complex::default(); FFT_SIZE],
+            scratch,
+        }
+    }
+
+    pub fn path(&self) -> Option<&str> {
+        self.path.as_deref()
+    }
+
+    pub fn sample_rate(&self) -> f32 {
+        self.sample_rate
+    }
+
+    /// Number of partitions needed to cover `length_ms` of the impulse response.
+    pub fn partitions_for_length(&self, length_ms: f32) -> usize {
+        let samples = (length_ms / 1000.0 * self.sample_rate) as usize;
+        samples.div_ceil(PARTITION_SIZE).min(self.num_partitions)
+    }
+
+    /// Convolves one channel in place. `trim` scales the convolved signal before it is mixed
+    /// with the (equally delayed) input by `mix`.
+    pub fn process(
+        &mut self,
+        channel: usize,
+        block: &mut [f32],
+        active_partitions: usize,
+        trim: f32,
+        mix: f32,
+    ) {
+        for sample in block.iter_mut() {
+            let conv = &mut self.channels[channel];
+            let pos = conv.fifo_pos;
+            let dry = conv.input[pos];
+            let wet = conv.output[pos];
+
+            conv.input[PARTITION_SIZE + pos] = *sample;
+            conv.fifo_pos += 1;
+
+            *sample = if self.kernels.is_empty() {
+                dry
+            } else {
+                dry * (1.0 - mix) + wet * trim * mix
+            };
+
+            if conv.fifo_pos == PARTITION_SIZE {
+                self.process_partition(channel, active_partitions);
+            }
+        }
+    }
+
+    fn process_partition(&mut self, channel: usize, active_partitions: usize) {
+        let conv = &mut self.channels[channel];
+        conv.fifo_pos = 0;
+
+        if self.num_partitions > 0 {
+            self.fft_buffer
+                .iter_mut()
+                .zip(conv.input.iter())
+                .for_each(|(bin, x)| *bin = Complex::new(*x, 0.0));
+            self.fft
+                .process_with_scratch(&mut self.fft_buffer, &mut self.scratch);
+
+            let fdl_offset = conv.fdl_pos * FFT_SIZE;
+            conv.fdl[fdl_offset..fdl_offset + FFT_SIZE].copy_from_slice(&self.fft_buffer);
+
+            // a mono IR is shared by both channels
+            let kernel = &self.kernels[channel.min(self.kernels.len() - 1)];
+
+            self.fft_buffer.fill(Complex::default());
+            for partition in 0..active_partitions.min(self.num_partitions) {
+                let fdl_idx =
+                    (conv.fdl_pos + self.num_partitions - partition) % self.num_partitions;
+                let x = &conv.fdl[fdl_idx * FFT_SIZE..(fdl_idx + 1) * FFT_SIZE];
+                let h = &kernel[partition * FFT_SIZE..(partition + 1) * FFT_SIZE];
+
+                self.fft_buffer
+                    .iter_mut()
+                    .zip(x.iter().zip(h.iter()))
+                    .for_each(|(acc, (x, h))| *acc += x * h);
+            }
+
+            self.ifft
+                .process_with_scratch(&mut self.fft_buffer, &mut self.scratch);
+
+            // overlap-save: only the second half is free of circular wrap around
+            let norm = (FFT_SIZE as f32).recip();
+            conv.output
+                .iter_mut()
+                .zip(self.fft_buffer[PARTITION_SIZE..].iter())
+                .for_each(|(y, bin)| *y = bin.re * norm);
+
+            conv.fdl_pos = (conv.fdl_pos + 1) % self.num_partitions;
+        }
+
+        conv.input.copy_within(PARTITION_SIZE.., 0);
+    }
+
+    pub fn reset(&mut self) {
+        self.channels.iter_mut().for_each(|conv| {
+            conv.input.fill(0.0);
+            conv.output.fill(0.0);
+            conv.fifo_pos = 0;
+            conv.fdl.fill(Complex::default());
+            conv.fdl_pos = 0;
+        });
+    }
+}
+
+/// Hands freshly loaded cabinets to the audio thread without allocating or freeing there. The
+/// replaced cabinet is parked in `outgoing` and dropped by the next load on the background
+/// thread.
+#[derive(Default)]
+pub struct CabinetExchange {
+    incoming: Mutex<Option<Box<Cabinet>>>,
+    outgoing: Mutex<Option<Box<Cabinet>>>,
+}
+
+impl CabinetExchange {
+    /// Called from the background thread.
+    pub fn submit(&self, cabinet: Cabinet) {
+        drop(self.outgoing.lock().unwrap().take());
+        *self.incoming.lock().unwrap() = Some(Box::new(cabinet));
+    }
+
+    /// Called from the audio thread. Swaps in a pending cabinet if neither slot is contended.
+    pub fn try_swap(&self, current: &mut Box<Cabinet>) {
+        let Ok(mut incoming) = self.incoming.try_lock() else {
+            return;
+        };
+        if incoming.is_none() {
+            return;
+        }
+        let Ok(mut outgoing) = self.outgoing.try_lock() else {
+            return;
+        };
+        if outgoing.is_some() {
+            return;
+        }
+
+        if let Some(cabinet) = incoming.take() {
+            *outgoing = Some(std::mem::replace(current, cabinet));
+        }
+    }
+}
+
+/// Windowed sinc resampling, band limited to the lower of the two Nyquist frequencies.
+fn resample(input: &[f32], from_rate: f32, to_rate: f32) -> Vec<f32> {
+    if (from_rate - to_rate).abs() < f32::EPSILON || input.is_empty() {
+        return input.to_vec();
+    }
+
+    let ratio = to_rate / from_rate;
+    let cutoff = ratio.min(1.0);
+    let out_len = (input.len() as f32 * ratio).ceil() as usize;
+
+    (0..out_len)
+        .map(|n| {
+            let t = n as f32 / ratio;
+            let centre = t.floor() as isize;
+
+            (centre - RESAMPLE_TAPS + 1..=centre + RESAMPLE_TAPS)
+                .filter(|j| *j >= 0 && (*j as usize) < input.len())
+                .map(|j| {
+                    let x = t - j as f32;
+                    let sinc = if x.abs() < 1e-6 {
+                        1.0
+                    } else {
+                        (PI * cutoff * x).sin() / (PI * cutoff * x)
+                    };
+                    // Blackman window over the kernel span
+                    let w = 0.5 + x / (2.0 * RESAMPLE_TAPS as f32);
+                    let window = 0.42 - 0.5 * (2.0 * PI * w).cos() + 0.08 * (4.0 * PI * w).cos();
+
+                    input[j as usize] * cutoff * sinc * window
+                })
+                .sum()
+        })
+        .collect()
+}
+
+/// Emits `event` with the loaded impulse response's path whenever a background load or clear
+/// has changed it, checking every [`WATCH_INTERVAL`].
+pub fn watch_ir_path<E, F>(
+    cx: &mut Context,
+    params: Arc<NonlinearAdaaParams>,
+    alive: Weak<()>,
+    event: F,
+) where
+    E: Send + 'static,
+    F: Fn(Option<String>) -> E + Send + 'static,
+{
+    let mut shown = params.ir_path.read().unwrap().clone();
+    editor::poll_while_open(cx, alive, WATCH_INTERVAL, move || {
+        let ir_path = params.ir_path.read().unwrap().clone();
+        if ir_path == shown {
+            return None;
+        }
+        shown = ir_path.clone();
+        Some(event(ir_path))
+    });
+}
@@ -0,0 +1,174 @@
+//! Output ceiling applied after the dry/wet mix.
+
+use jdsp::{Oversample, OversampleFactor};
+use nih_plug::prelude::*;
+
+use crate::MAX_BLOCK_SIZE;
+
+const LOOKAHEAD_MS: f32 = 1.5;
+const CLIPPER_OS_FACTOR: OversampleFactor = OversampleFactor::FourTimes;
+const CLIPPER_OS_TIMES: usize = 4;
+
+#[derive(Enum, Debug, PartialEq, Eq, Clone, Copy)]
+pub enum CeilingMode {
+    #[name = "Off"]
+    Off,
+    #[name = "Limiter"]
+    Limiter,
+    #[name = "Hard Clip"]
+    HardClip,
+}
+
+/// Stereo linked look-ahead peak limiter. The required gain is held for the look-ahead window
+/// and then averaged over the same window, so the gain has fully reached its target by the time
+/// the peak leaves the delay line.
+struct LookaheadLimiter {
+    lookahead: usize,
+    delay: [Vec<f32>; 2],
+    required_gain: Vec<f32>,
+    smoothing: Vec<f32>,
+    smoothing_sum: f32,
+    pos: usize,
+    release_gain: f32,
+}
+
+impl LookaheadLimiter {
+    fn new(lookahead: usize) -> Self {
+        let len = lookahead.max(1);
+        Self {
+            lookahead: len,
+            delay: [vec![0.0; len], vec![0.0; len]],
+            required_gain: vec![1.0; len],
+            smoothing: vec![1.0; len],
+            smoothing_sum: len as f32,
+            pos: 0,
+            release_gain: 1.0,
+        }
+    }
+
+    #[inline]
+    fn process(&mut self, l: &mut f32, r: &mut f32, ceiling: f32, release_coeff: f32) {
+        // hold over the previous `lookahead` samples before counting this one, so a sample's
+        // requirement is part of every gain averaged in while it sits in the delay line
+        let held = self.required_gain.iter().copied().fold(1.0_f32, f32::min);
+
+        let peak = l.abs().max(r.abs());
+        self.required_gain[self.pos] = if peak > ceiling { ceiling / peak } else { 1.0 };
+
+        // attack instantly, recover with a one pole release
+        self.release_gain = if held < self.release_gain {
+            held
+        } else {
+            held + release_coeff * (self.release_gain - held)
+        };
+
+        self.smoothing_sum += self.release_gain - self.smoothing[self.pos];
+        self.smoothing[self.pos] = self.release_gain;
+        let gain = self.smoothing_sum / self.lookahead as f32;
+
+        let delayed_l = std::mem::replace(&mut self.delay[0][self.pos], *l);
+        let delayed_r = std::mem::replace(&mut self.delay[1][self.pos], *r);
+        *l = delayed_l * gain;
+        *r = delayed_r * gain;
+
+        self.pos = (self.pos + 1) % self.lookahead;
+    }
+
+    fn reset(&mut self) {
+        self.delay.iter_mut().for_each(|d| d.fill(0.0));
+        self.required_gain.fill(1.0);
+        self.smoothing.fill(1.0);
+        self.smoothing_sum = self.lookahead as f32;
+        self.pos = 0;
+        self.release_gain = 1.0;
+    }
+}
+
+pub struct OutputCeiling {
+    limiter: LookaheadLimiter,
+    clippers: [Oversample; 2],
+    clip_buf: [[f32; MAX_BLOCK_SIZE * CLIPPER_OS_TIMES]; 2],
+    sample_rate: f32,
+}
+
+impl Default for OutputCeiling {
+    fn default() -> Self {
+        let mut clippers = [
+            Oversample::new(CLIPPER_OS_FACTOR, MAX_BLOCK_SIZE),
+            Oversample::new(CLIPPER_OS_FACTOR, MAX_BLOCK_SIZE),
+        ];
+        clippers
+            .iter_mut()
+            .for_each(|os| os.initialize_oversample_stages());
+
+        Self {
+            limiter: LookaheadLimiter::new(lookahead_samples(44100.0)),
+            clippers,
+            clip_buf: [[0.0; MAX_BLOCK_SIZE * CLIPPER_OS_TIMES]; 2],
+            sample_rate: 44100.0,
+        }
+    }
+}
+
+fn lookahead_samples(sample_rate: f32) -> usize {
+    (LOOKAHEAD_MS / 1000.0 * sample_rate).round() as usize
+}
+
+impl OutputCeiling {
+    pub fn initialize(&mut self, sample_rate: f32) {
+        if sample_rate != self.sample_rate {
+            self.sample_rate = sample_rate;
+            self.limiter = LookaheadLimiter::new(lookahead_samples(sample_rate));
+        }
+    }
+
+    pub fn latency(&self, mode: CeilingMode) -> usize {
+        match mode {
+            CeilingMode::Off => 0,
+            CeilingMode::Limiter => self.limiter.lookahead,
+            CeilingMode::HardClip => self.clippers[0].get_latency_samples(),
+        }
+    }
+
+    /// Per sample release coefficient for a release time in milliseconds.
+    pub fn release_coeff(&self, release_ms: f32) -> f32 {
+        (-1.0 / (release_ms / 1000.0 * self.sample_rate)).exp()
+    }
+
+    pub fn process(
+        &mut self,
+        mode: CeilingMode,
+        left: &mut [f32],
+        right: &mut [f32],
+        ceiling: f32,
+        release_coeff: f32,
+    ) {
+        match mode {
+            CeilingMode::Off => (),
+            CeilingMode::Limiter => left
+                .iter_mut()
+                .zip(right.iter_mut())
+                .for_each(|(l, r)| self.limiter.process(l, r, ceiling, release_coeff)),
+            CeilingMode::HardClip => {
+                let samples_to_take = left.len() * CLIPPER_OS_TIMES;
+
+                for (channel, (os, buf)) in [left, right]
+                    .into_iter()
+                    .zip(self.clippers.iter_mut().zip(self.clip_buf.iter_mut()))
+                {
+                    os.process_up(channel, buf);
+                    buf.iter_mut()
+                        .take(samples_to_take)
+                        .for_each(|x| *x = x.clamp(-ceiling, ceiling));
+                    os.process_down(buf, channel);
+                }
+            }
+        }
+    }
+
+    pub fn reset(&mut self) {
+        self.limiter.reset();
+        self.clippers.iter_mut().for_each(|os| os.reset());
+        self.clip_buf.iter_mut().for_each(|buf| buf.fill(0.0));
+    }
+}
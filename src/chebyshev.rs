@@ -0,0 +1,220 @@
+//! Waveshaper built from weighted Chebyshev polynomials. Driving `T_k` with a full scale sine
+//! produces exactly its `k`th harmonic, so each harmonic's level can be set directly.
+//!
+//! The shaper is a polynomial on `-1..=1` and holds its end values past it, like a hard clip, so
+//! both of its anti-derivatives are available in closed form and the first and second order ADAA
+//! used for the other curves apply here as well.
+
+use jdsp::AntiderivativeOrder;
+use nih_plug::prelude::*;
+
+use crate::midi::MidiCcMap;
+
+pub const FIRST_HARMONIC: usize = 2;
+pub const LAST_HARMONIC: usize = 8;
+pub const NUM_HARMONICS: usize = LAST_HARMONIC - FIRST_HARMONIC + 1;
+
+// below this input difference the ADAA quotients fall back to evaluating at the midpoint
+const ILL_CONDITIONED: f64 = 1e-5;
+
+/// Level and phase of one added harmonic.
+#[derive(Params, Debug)]
+pub struct HarmonicParams {
+    #[id = "level"]
+    pub level: FloatParam,
+    #[id = "invert"]
+    pub invert: BoolParam,
+}
+
+impl HarmonicParams {
+    pub fn new(harmonic: usize) -> Self {
+        Self {
+            level: FloatParam::new(
+                format!("Harmonic {harmonic} Level"),
+                0.0,
+                FloatRange::Linear { min: 0.0, max: 1.0 },
+            )
+            .with_value_to_string(formatters::v2s_f32_percentage(1))
+            .with_string_to_value(formatters::s2v_f32_percentage()),
+
+            invert: BoolParam::new(format!("Harmonic {harmonic} Invert"), false),
+        }
+    }
+
+    /// Signed weight of the harmonic's polynomial, with the values CCs have set.
+    pub fn weight(&self, midi_map: &MidiCcMap) -> f32 {
+        let level = midi_map.value(&self.level);
+        if midi_map.value(&self.invert) {
+            -level
+        } else {
+            level
+        }
+    }
+}
+
+/// Power series coefficients of the shaper and its first two anti-derivatives.
+#[derive(Clone, Copy, Debug)]
+pub struct ChebyshevShape {
+    p: [f64; LAST_HARMONIC + 1],
+    f1: [f64; LAST_HARMONIC + 2],
+    f2: [f64; LAST_HARMONIC + 3],
+}
+
+impl Default for ChebyshevShape {
+    fn default() -> Self {
+        Self::new(&[0.0; NUM_HARMONICS])
+    }
+}
+
+#[inline]
+fn horner(coefficients: &[f64], x: f64) -> f64 {
+    coefficients.iter().rev().fold(0.0, |acc, c| acc * x + c)
+}
+
+impl ChebyshevShape {
+    /// `weights[0]` is the 2nd harmonic's weight. The fundamental always passes at unity.
+    pub fn new(weights: &[f32; NUM_HARMONICS]) -> Self {
+        // T_0 and T_1, then T_{k + 1} = 2x T_k - T_{k - 1}
+        let mut t_prev = [0.0; LAST_HARMONIC + 1];
+        let mut t = [0.0; LAST_HARMONIC + 1];
+        t_prev[0] = 1.0;
+        t[1] = 1.0;
+
+        let mut p = t;
+        for k in 2..=LAST_HARMONIC {
+            let mut t_next = [0.0; LAST_HARMONIC + 1];
+            for i in 0..LAST_HARMONIC {
+                t_next[i + 1] = 2.0 * t[i];
+            }
+            t_next
+                .iter_mut()
+                .zip(t_prev.iter())
+                .for_each(|(next, prev)| *next -= prev);
+
+            let weight = weights[k - FIRST_HARMONIC] as f64;
+            p.iter_mut()
+                .zip(t_next.iter())
+                .for_each(|(p, t)| *p += weight * t);
+
+            t_prev = t;
+            t = t_next;
+        }
+
+        // even harmonics add DC, keep silence silent
+        p[0] = 0.0;
+
+        let mut f1 = [0.0; LAST_HARMONIC + 2];
+        p.iter()
+            .enumerate()
+            .for_each(|(i, c)| f1[i + 1] = c / (i + 1) as f64);
+        let mut f2 = [0.0; LAST_HARMONIC + 3];
+        f1.iter()
+            .enumerate()
+            .for_each(|(i, c)| f2[i + 1] = c / (i + 1) as f64);
+
+        Self { p, f1, f2 }
+    }
+
+    /// The shaper itself.
+    #[inline]
+    pub fn eval(&self, x: f64) -> f64 {
+        horner(&self.p, x.clamp(-1.0, 1.0))
+    }
+
+    #[inline]
+    fn antiderivative_1(&self, x: f64) -> f64 {
+        let edge = x.clamp(-1.0, 1.0);
+        let d = x - edge;
+        horner(&self.f1, edge) + horner(&self.p, edge) * d
+    }
+
+    #[inline]
+    fn antiderivative_2(&self, x: f64) -> f64 {
+        let edge = x.clamp(-1.0, 1.0);
+        let d = x - edge;
+        horner(&self.f2, edge) + horner(&self.f1, edge) * d + horner(&self.p, edge) * d * d / 2.0
+    }
+}
+
+/// Chebyshev shaper with first or second order anti-derivative anti-aliasing.
+pub struct ChebyshevProcessor {
+    shape: ChebyshevShape,
+    order: AntiderivativeOrder,
+    x1: f64,
+    x2: f64,
+}
+
+impl Default for ChebyshevProcessor {
+    fn default() -> Self {
+        Self {
+            shape: ChebyshevShape::default(),
+            order: AntiderivativeOrder::FirstOrder,
+            x1: 0.0,
+            x2: 0.0,
+        }
+    }
+}
+
+impl ChebyshevProcessor {
+    pub fn set_shape(&mut self, shape: ChebyshevShape) {
+        self.shape = shape;
+    }
+
+    pub fn set_order(&mut self, order: AntiderivativeOrder) {
+        self.order = order;
+    }
+
+    /// Divided difference of the second anti-derivative, falling back to the first
+    /// anti-derivative at the midpoint when the inputs are too close.
+    #[inline]
+    fn f2_difference(&self, a: f64, b: f64) -> f64 {
+        if (a - b).abs() > ILL_CONDITIONED {
+            (self.shape.antiderivative_2(a) - self.shape.antiderivative_2(b)) / (a - b)
+        } else {
+            self.shape.antiderivative_1((a + b) / 2.0)
+        }
+    }
+
+    #[inline]
+    pub fn process(&mut self, x: f32) -> f32 {
+        let x = x as f64;
+        let shape = &self.shape;
+
+        let y = match self.order {
+            AntiderivativeOrder::FirstOrder => {
+                if (x - self.x1).abs() > ILL_CONDITIONED {
+                    (shape.antiderivative_1(x) - shape.antiderivative_1(self.x1)) / (x - self.x1)
+                } else {
+                    shape.eval((x + self.x1) / 2.0)
+                }
+            }
+            AntiderivativeOrder::SecondOrder => {
+                if (x - self.x2).abs() > ILL_CONDITIONED {
+                    2.0 * (self.f2_difference(x, self.x1) - self.f2_difference(self.x1, self.x2))
+                        / (x - self.x2)
+                } else {
+                    let x_bar = (x + self.x2) / 2.0;
+                    let delta = x_bar - self.x1;
+                    if delta.abs() > ILL_CONDITIONED {
+                        2.0 / delta
+                            * (shape.antiderivative_1(x_bar)
+                                + (shape.antiderivative_2(self.x1) - shape.antiderivative_2(x_bar))
+                                    / delta)
+                    } else {
+                        shape.eval((x_bar + self.x1) / 2.0)
+                    }
+                }
+            }
+        };
+
+        self.x2 = self.x1;
+        self.x1 = x;
+
+        y as f32
+    }
+
+    pub fn reset(&mut self) {
+        self.x1 = 0.0;
+        self.x2 = 0.0;
+    }
+}
@@ -0,0 +1,183 @@
+use jdsp::AntiderivativeOrder;
+use std::f32::consts::FRAC_PI_2;
+
+use crate::styles::{ClipStyle, StyleProcessor, StyleSettings};
+
+const CROSSFADE_MS: f32 = 10.0;
+// input replayed into an incoming bank before it is faded in. Covers the diode clipper's
+// capacitor many times over and lets the hysteresis model settle onto the recent loop.
+const PRIME_MS: f32 = 2.0;
+// inputs kept for priming, 2 ms at 16 times 96 kHz
+const MAX_PRIME_SAMPLES: usize = 4096;
+const NUM_BANKS: usize = 3;
+
+/// A nonlinear processor that changes style or ADAA order without a discontinuity. On a state
+/// change an idle processor bank takes the new state, is primed with the last [`PRIME_MS`] of
+/// input so the style's memory is already warm, and is faded in with an equal power crossfade
+/// while the outgoing bank keeps running.
+///
+/// A change that arrives mid-fade freezes the fade where it is and fades the third bank in
+/// against that mix. One more change while all three banks are busy waits for the fade to end.
+pub struct CrossfadingProcessor {
+    banks: [StyleProcessor; NUM_BANKS],
+    // the bank faded in, or playing on its own while not fading
+    active: usize,
+    active_state: (ClipStyle, AntiderivativeOrder),
+    // the state last asked for, which may still be waiting for a free bank
+    target: (ClipStyle, AntiderivativeOrder),
+    // gain of each bank in the mix that is fading out
+    outgoing: [f32; NUM_BANKS],
+    fade_len: usize,
+    // equal to `fade_len` while not fading
+    fade_pos: usize,
+    prime_len: usize,
+    // recent inputs, the oldest at `history_pos`
+    history: Box<[f32; MAX_PRIME_SAMPLES]>,
+    history_pos: usize,
+}
+
+impl Default for CrossfadingProcessor {
+    fn default() -> Self {
+        let state = (ClipStyle::HardClip, AntiderivativeOrder::FirstOrder);
+        let fade_len = fade_samples(44100.0);
+        Self {
+            banks: Default::default(),
+            active: 0,
+            active_state: state,
+            target: state,
+            outgoing: [0.0; NUM_BANKS],
+            fade_len,
+            fade_pos: fade_len,
+            prime_len: prime_samples(44100.0),
+            history: Box::new([0.0; MAX_PRIME_SAMPLES]),
+            history_pos: 0,
+        }
+    }
+}
+
+fn fade_samples(sample_rate: f32) -> usize {
+    ((CROSSFADE_MS / 1000.0 * sample_rate) as usize).max(1)
+}
+
+fn prime_samples(sample_rate: f32) -> usize {
+    ((PRIME_MS / 1000.0 * sample_rate) as usize).clamp(2, MAX_PRIME_SAMPLES)
+}
+
+impl CrossfadingProcessor {
+    /// Sets the (oversampled) rate the processor runs at, which determines the fade length.
+    pub fn set_sample_rate(&mut self, sample_rate: f32) {
+        let fading = self.fading();
+        self.fade_len = fade_samples(sample_rate);
+        self.fade_pos = if fading {
+            self.fade_pos.min(self.fade_len - 1)
+        } else {
+            self.fade_len
+        };
+        self.prime_len = prime_samples(sample_rate);
+        self.banks
+            .iter_mut()
+            .for_each(|bank| bank.set_sample_rate(sample_rate));
+    }
+
+    pub fn set_settings(&mut self, settings: &StyleSettings) {
+        self.banks
+            .iter_mut()
+            .for_each(|bank| bank.set_settings(settings));
+    }
+
+    /// Starts a crossfade to the given state if it differs from the current one.
+    pub fn set_state(&mut self, style: ClipStyle, order: AntiderivativeOrder) {
+        if (style, order) == self.target {
+            return;
+        }
+        self.target = (style, order);
+
+        if !self.fading() {
+            self.outgoing = [0.0; NUM_BANKS];
+            self.outgoing[self.active] = 1.0;
+            self.fade_in_target();
+        } else if self.outgoing.iter().filter(|gain| **gain != 0.0).count() < NUM_BANKS - 1 {
+            // freeze the fade, the outgoing and incoming banks fade out together from here
+            let (fade_in, fade_out) = self.fade_gains();
+            self.outgoing.iter_mut().for_each(|gain| *gain *= fade_out);
+            self.outgoing[self.active] = fade_in;
+            self.fade_in_target();
+        }
+    }
+
+    fn fading(&self) -> bool {
+        self.fade_pos < self.fade_len
+    }
+
+    /// Equal power gains of the incoming bank and the outgoing mix.
+    fn fade_gains(&self) -> (f32, f32) {
+        (FRAC_PI_2 * self.fade_pos as f32 / self.fade_len as f32).sin_cos()
+    }
+
+    /// Primes an idle bank with the target state and starts fading it in.
+    fn fade_in_target(&mut self) {
+        let Some(incoming) =
+            (0..NUM_BANKS).find(|bank| *bank != self.active && self.outgoing[*bank] == 0.0)
+        else {
+            return;
+        };
+
+        let (style, order) = self.target;
+        let bank = &mut self.banks[incoming];
+        bank.reset();
+        bank.set_state(style, order);
+        let start = self.history_pos + MAX_PRIME_SAMPLES - self.prime_len;
+        (start..start + self.prime_len).for_each(|i| {
+            bank.process(self.history[i % MAX_PRIME_SAMPLES]);
+        });
+
+        self.active = incoming;
+        self.active_state = self.target;
+        self.fade_pos = 0;
+    }
+
+    #[inline]
+    pub fn process(&mut self, x: f32) -> f32 {
+        self.history[self.history_pos] = x;
+        self.history_pos = (self.history_pos + 1) % MAX_PRIME_SAMPLES;
+
+        if !self.fading() {
+            return self.banks[self.active].process(x);
+        }
+
+        let outgoing: f32 = self
+            .banks
+            .iter_mut()
+            .zip(self.outgoing)
+            .filter(|(_, gain)| *gain != 0.0)
+            .map(|(bank, gain)| gain * bank.process(x))
+            .sum();
+        let incoming = self.banks[self.active].process(x);
+
+        let (fade_in, fade_out) = self.fade_gains();
+        self.fade_pos += 1;
+        if !self.fading() {
+            self.outgoing = [0.0; NUM_BANKS];
+            // a change that found every bank busy starts now
+            if self.target != self.active_state {
+                self.outgoing[self.active] = 1.0;
+                self.fade_in_target();
+            }
+        }
+
+        outgoing * fade_out + incoming * fade_in
+    }
+
+    /// Clears all history and switches to the given state immediately.
+    pub fn reset(&mut self, style: ClipStyle, order: AntiderivativeOrder) {
+        self.banks.iter_mut().for_each(|bank| bank.reset());
+        self.banks[0].set_state(style, order);
+        self.active = 0;
+        self.active_state = (style, order);
+        self.target = (style, order);
+        self.outgoing = [0.0; NUM_BANKS];
+        self.fade_pos = self.fade_len;
+        self.history.fill(0.0);
+        self.history_pos = 0;
+    }
+}
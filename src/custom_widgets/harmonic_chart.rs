@@ -0,0 +1,82 @@
+use nih_plug_vizia::vizia::prelude::*;
+use nih_plug_vizia::vizia::vg;
+
+use crate::analysis::HarmonicAnalysis;
+
+/// Level at the bottom of the chart, relative to the fundamental.
+const FLOOR_DB: f64 = -120.0;
+
+/// Bar chart of the levels of harmonics 2 to 10 relative to the fundamental, from 0 dB at the top
+/// to [`FLOOR_DB`] at the bottom. A horizontal line marks the energy off the harmonics, which is
+/// mostly aliasing.
+pub struct HarmonicChart<L: Lens<Target = Option<HarmonicAnalysis>>> {
+    analysis: L,
+}
+
+impl<L: Lens<Target = Option<HarmonicAnalysis>>> HarmonicChart<L> {
+    pub fn new(cx: &mut Context, analysis: L) -> Handle<Self> {
+        Self { analysis }.build(cx, |_| {})
+    }
+}
+
+impl<L: Lens<Target = Option<HarmonicAnalysis>>> View for HarmonicChart<L> {
+    fn element(&self) -> Option<&'static str> {
+        Some("harmonic-chart")
+    }
+
+    fn draw(&self, cx: &mut DrawContext, canvas: &mut Canvas) {
+        let bounds = cx.bounds();
+        if bounds.w == 0.0 || bounds.h == 0.0 {
+            return;
+        }
+
+        let to_y = |db: f64| {
+            let height = (1.0 - db.clamp(FLOOR_DB, 0.0) / FLOOR_DB) as f32;
+            bounds.y + bounds.h * (1.0 - height)
+        };
+
+        let mut baseline = vg::Path::new();
+        baseline.move_to(bounds.x, bounds.y + bounds.h);
+        baseline.line_to(bounds.x + bounds.w, bounds.y + bounds.h);
+        canvas.stroke_path(
+            &mut baseline,
+            &vg::Paint::color(vg::Color::rgba(128, 128, 128, 128)).with_line_width(1.0),
+        );
+
+        let Some(analysis) = self.analysis.get(cx) else {
+            return;
+        };
+
+        // the theme sets the bars' color
+        let color = cx.font_color();
+        let bar_color = vg::Color::rgba(color.r(), color.g(), color.b(), color.a());
+
+        let slot = bounds.w / analysis.harmonics_db.len() as f32;
+        let mut bars = vg::Path::new();
+        analysis
+            .harmonics_db
+            .iter()
+            .enumerate()
+            .for_each(|(i, level)| {
+                if let Some(db) = level {
+                    let top = to_y(*db);
+                    bars.rect(
+                        bounds.x + (i as f32 + 0.2) * slot,
+                        top,
+                        0.6 * slot,
+                        bounds.y + bounds.h - top,
+                    );
+                }
+            });
+        canvas.fill_path(&mut bars, &vg::Paint::color(bar_color));
+
+        let alias_y = to_y(analysis.alias_db);
+        let mut alias = vg::Path::new();
+        alias.move_to(bounds.x, alias_y);
+        alias.line_to(bounds.x + bounds.w, alias_y);
+        canvas.stroke_path(
+            &mut alias,
+            &vg::Paint::color(vg::Color::rgba(200, 60, 60, 220)).with_line_width(1.5),
+        );
+    }
+}
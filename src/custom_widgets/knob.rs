@@ -0,0 +1,260 @@
+use nih_plug::prelude::Param;
+use nih_plug_vizia::vizia::prelude::*;
+use nih_plug_vizia::vizia::vg;
+use nih_plug_vizia::widgets::param_base::ParamWidgetBase;
+use std::f32::consts::PI;
+
+/// Pixels of vertical drag that sweep the whole range.
+const DRAG_RANGE_PX: f32 = 200.0;
+/// Holding shift while dragging slows the knob down by this factor.
+const FINE_DRAG_FACTOR: f32 = 0.1;
+// the arc starts at 7 o'clock and ends at 5 o'clock
+const ARC_START: f32 = 0.75 * PI;
+const ARC_SWEEP: f32 = 1.5 * PI;
+
+/// A rotary knob for a parameter. Drag vertically to change it, hold shift for fine
+/// adjustments, double click or command/ctrl click to reset it and scroll to step through its
+/// values. The value is shown under the knob while it is being adjusted.
+#[derive(Lens)]
+pub struct ParamKnob {
+    param_base: ParamWidgetBase,
+    drag_active: bool,
+    drag_status: Option<DragStatus>,
+    // fractional scroll wheel lines not yet applied
+    scrolled_lines: f32,
+}
+
+struct DragStatus {
+    starting_y: f32,
+    starting_value: f32,
+    fine: bool,
+}
+
+impl ParamKnob {
+    pub fn new<L, Params, P, FMap>(
+        cx: &mut Context,
+        params: L,
+        params_to_param: FMap,
+    ) -> Handle<Self>
+    where
+        L: Lens<Target = Params> + Clone,
+        Params: 'static,
+        P: Param + 'static,
+        FMap: Fn(&Params) -> &P + Copy + 'static,
+    {
+        Self {
+            param_base: ParamWidgetBase::new(cx, params.clone(), params_to_param),
+            drag_active: false,
+            drag_status: None,
+            scrolled_lines: 0.0,
+        }
+        .build(
+            cx,
+            ParamWidgetBase::build_view(params, params_to_param, move |cx, param_data| {
+                let name = param_data.param().name().to_owned();
+                let normalized_lens =
+                    param_data.make_lens(|param| param.unmodulated_normalized_value());
+                let display_lens = param_data.make_lens(|param| {
+                    param.normalized_value_to_string(param.unmodulated_normalized_value(), true)
+                });
+
+                VStack::new(cx, |cx| {
+                    // rebuilt on every change so the arc is redrawn
+                    Binding::new(cx, normalized_lens, |cx, normalized| {
+                        KnobArc {
+                            normalized: normalized.get(cx),
+                        }
+                        .build(cx, |_| {})
+                        .class("knob-arc")
+                        .width(Pixels(40.0))
+                        .height(Pixels(40.0))
+                        .left(Stretch(1.0))
+                        .right(Stretch(1.0));
+                    });
+
+                    Label::new(cx, &name)
+                        .class("knob-label")
+                        .font_size(12.0)
+                        .left(Stretch(1.0))
+                        .right(Stretch(1.0));
+
+                    Label::new(cx, display_lens)
+                        .class("knob-value")
+                        .font_size(12.0)
+                        .left(Stretch(1.0))
+                        .right(Stretch(1.0))
+                        .display(ParamKnob::drag_active);
+                })
+                .row_between(Pixels(2.0));
+            }),
+        )
+        .width(Pixels(80.0))
+        .height(Auto)
+    }
+
+    fn set_from_drag(&self, cx: &mut EventContext, y: f32) {
+        if let Some(status) = &self.drag_status {
+            let scale = if status.fine { FINE_DRAG_FACTOR } else { 1.0 };
+            let delta = (status.starting_y - y) / (DRAG_RANGE_PX * cx.scale_factor());
+            let value = (status.starting_value + delta * scale).clamp(0.0, 1.0);
+            self.param_base.set_normalized_value(cx, value);
+        }
+    }
+
+    /// Restarts the drag from the current position so toggling shift does not make the knob
+    /// jump.
+    fn restart_drag(&mut self, cx: &mut EventContext, fine: bool) {
+        if self.drag_status.is_some() {
+            self.drag_status = Some(DragStatus {
+                starting_y: cx.mouse().cursory,
+                starting_value: self.param_base.unmodulated_normalized_value(),
+                fine,
+            });
+        }
+    }
+
+    fn reset(&self, cx: &mut EventContext) {
+        self.param_base.begin_set_parameter(cx);
+        self.param_base
+            .set_normalized_value(cx, self.param_base.default_normalized_value());
+        self.param_base.end_set_parameter(cx);
+    }
+}
+
+impl View for ParamKnob {
+    fn element(&self) -> Option<&'static str> {
+        Some("param-knob")
+    }
+
+    fn event(&mut self, cx: &mut EventContext, event: &mut Event) {
+        event.map(|window_event, meta| match window_event {
+            WindowEvent::MouseDown(MouseButton::Left) => {
+                if cx.modifiers().command() {
+                    self.reset(cx);
+                } else {
+                    self.drag_active = true;
+                    self.drag_status = Some(DragStatus {
+                        starting_y: cx.mouse().cursory,
+                        starting_value: self.param_base.unmodulated_normalized_value(),
+                        fine: cx.modifiers().shift(),
+                    });
+                    cx.capture();
+                    cx.focus();
+                    cx.set_active(true);
+                    self.param_base.begin_set_parameter(cx);
+                }
+                meta.consume();
+            }
+            WindowEvent::MouseDoubleClick(MouseButton::Left) => {
+                // the first click of the double click started a drag, end it before resetting
+                if self.drag_active {
+                    self.drag_active = false;
+                    self.drag_status = None;
+                    cx.release();
+                    cx.set_active(false);
+                    self.param_base.end_set_parameter(cx);
+                }
+                self.reset(cx);
+                meta.consume();
+            }
+            WindowEvent::MouseUp(MouseButton::Left) => {
+                if self.drag_active {
+                    self.drag_active = false;
+                    self.drag_status = None;
+                    cx.release();
+                    cx.set_active(false);
+                    self.param_base.end_set_parameter(cx);
+                    meta.consume();
+                }
+            }
+            WindowEvent::MouseMove(_x, y) => {
+                if self.drag_active {
+                    self.set_from_drag(cx, *y);
+                }
+            }
+            WindowEvent::KeyDown(_, Some(Key::Shift)) => self.restart_drag(cx, true),
+            WindowEvent::KeyUp(_, Some(Key::Shift)) => self.restart_drag(cx, false),
+            WindowEvent::MouseScroll(_scroll_x, scroll_y) => {
+                self.scrolled_lines += scroll_y;
+                if self.scrolled_lines.abs() >= 1.0 {
+                    let fine = cx.modifiers().shift();
+                    self.param_base.begin_set_parameter(cx);
+
+                    let mut value = self.param_base.unmodulated_normalized_value();
+                    while self.scrolled_lines >= 1.0 {
+                        value = self.param_base.next_normalized_step(value, fine);
+                        self.scrolled_lines -= 1.0;
+                    }
+                    while self.scrolled_lines <= -1.0 {
+                        value = self.param_base.previous_normalized_step(value, fine);
+                        self.scrolled_lines += 1.0;
+                    }
+
+                    self.param_base.set_normalized_value(cx, value);
+                    self.param_base.end_set_parameter(cx);
+                }
+                meta.consume();
+            }
+            _ => {}
+        });
+    }
+}
+
+/// The knob's track and value arc.
+struct KnobArc {
+    normalized: f32,
+}
+
+impl View for KnobArc {
+    fn element(&self) -> Option<&'static str> {
+        Some("knob-arc")
+    }
+
+    fn draw(&self, cx: &mut DrawContext, canvas: &mut Canvas) {
+        let bounds = cx.bounds();
+        if bounds.w == 0.0 || bounds.h == 0.0 {
+            return;
+        }
+
+        let line_width = 4.0 * cx.scale_factor();
+        let radius = bounds.w.min(bounds.h) / 2.0 - line_width;
+        let (cx_, cy_) = (bounds.x + bounds.w / 2.0, bounds.y + bounds.h / 2.0);
+        let value_angle = ARC_START + ARC_SWEEP * self.normalized;
+        // the theme sets the knob's color
+        let color = cx.font_color();
+        let color = vg::Color::rgba(color.r(), color.g(), color.b(), color.a());
+
+        let mut track = vg::Path::new();
+        track.arc(
+            cx_,
+            cy_,
+            radius,
+            ARC_START,
+            ARC_START + ARC_SWEEP,
+            vg::Solidity::Hole,
+        );
+        canvas.stroke_path(
+            &mut track,
+            &vg::Paint::color(vg::Color::rgba(128, 128, 128, 96)).with_line_width(line_width),
+        );
+
+        let mut value = vg::Path::new();
+        value.arc(cx_, cy_, radius, ARC_START, value_angle, vg::Solidity::Hole);
+        canvas.stroke_path(
+            &mut value,
+            &vg::Paint::color(color).with_line_width(line_width),
+        );
+
+        // pointer from the centre towards the value
+        let mut pointer = vg::Path::new();
+        pointer.move_to(cx_, cy_);
+        pointer.line_to(
+            cx_ + radius * value_angle.cos(),
+            cy_ + radius * value_angle.sin(),
+        );
+        canvas.stroke_path(
+            &mut pointer,
+            &vg::Paint::color(color).with_line_width(line_width / 2.0),
+        );
+    }
+}
@@ -0,0 +1,38 @@
+use nih_plug::prelude::ParamPtr;
+use nih_plug_vizia::vizia::prelude::*;
+
+/// Sent when a [`MidiLearn`] control is right clicked.
+pub enum MidiLearnEvent {
+    Toggle(ParamPtr),
+}
+
+/// Wraps the control for a parameter and emits [`MidiLearnEvent::Toggle`] for it when the
+/// control is right clicked.
+pub struct MidiLearn {
+    param: ParamPtr,
+}
+
+impl MidiLearn {
+    pub fn new(
+        cx: &mut Context,
+        param: ParamPtr,
+        content: impl FnOnce(&mut Context),
+    ) -> Handle<Self> {
+        Self { param }.build(cx, content).width(Auto).height(Auto)
+    }
+}
+
+impl View for MidiLearn {
+    fn element(&self) -> Option<&'static str> {
+        Some("midi-learn")
+    }
+
+    fn event(&mut self, cx: &mut EventContext, event: &mut Event) {
+        event.map(|window_event, meta| {
+            if let WindowEvent::MouseDown(MouseButton::Right) = window_event {
+                cx.emit(MidiLearnEvent::Toggle(self.param));
+                meta.consume();
+            }
+        });
+    }
+}
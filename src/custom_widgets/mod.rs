@@ -0,0 +1,5 @@
+pub mod harmonic_chart;
+pub mod knob;
+pub mod midi_learn;
+pub mod oscilloscope;
+pub mod transfer_curve;
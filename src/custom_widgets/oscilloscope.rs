@@ -0,0 +1,145 @@
+use nih_plug_vizia::vizia::prelude::*;
+use nih_plug_vizia::vizia::vg;
+use std::sync::atomic::Ordering;
+use std::sync::Arc;
+
+use crate::scope::{Scope, SCOPE_CAPACITY};
+
+// the longest window still leaves room to search a full window back for a trigger
+const MAX_WINDOW_SAMPLES: usize = SCOPE_CAPACITY / 4;
+const MIN_WINDOW_SAMPLES: usize = 16;
+
+/// Zooms the scope by scrolling over it. Positive lines zoom in.
+pub enum OscilloscopeEvent {
+    Zoom(f32),
+}
+
+/// Shows the dry input and the processed signal, triggered on rising zero crossings of the input
+/// so periodic signals stand still. The oversampled output of the nonlinearity is drawn over them
+/// while its capture is enabled.
+pub struct Oscilloscope<W: Lens<Target = f32>> {
+    scope: Arc<Scope>,
+    window_ms: W,
+}
+
+impl<W: Lens<Target = f32>> Oscilloscope<W> {
+    pub fn new<L>(cx: &mut Context, scope: L, window_ms: W) -> Handle<Self>
+    where
+        L: Lens<Target = Arc<Scope>>,
+    {
+        Self {
+            scope: scope.get(cx),
+            window_ms,
+        }
+        .build(cx, |_| {})
+    }
+}
+
+/// Index of the newest rising zero crossing that still leaves `window` samples after it, or the
+/// newest full window if the signal never crosses.
+fn find_trigger(samples: &[f32], window: usize) -> usize {
+    let last = samples.len() - window;
+    (1..=last)
+        .rev()
+        .find(|&i| samples[i - 1] <= 0.0 && samples[i] > 0.0)
+        .unwrap_or(last)
+}
+
+fn stroke_trace(canvas: &mut Canvas, bounds: BoundingBox, samples: &[f32], paint: &vg::Paint) {
+    if samples.len() < 2 {
+        return;
+    }
+
+    // a couple of points per pixel is plenty
+    let step = (samples.len() / (2.0 * bounds.w).max(1.0) as usize).max(1);
+    let x_scale = bounds.w / (samples.len() - 1) as f32;
+    let to_y = |sample: f32| bounds.y + (1.0 - sample.clamp(-1.0, 1.0)) / 2.0 * bounds.h;
+
+    let mut path = vg::Path::new();
+    path.move_to(bounds.x, to_y(samples[0]));
+    (step..samples.len()).step_by(step).for_each(|i| {
+        path.line_to(bounds.x + i as f32 * x_scale, to_y(samples[i]));
+    });
+    canvas.stroke_path(&mut path, paint);
+}
+
+impl<W: Lens<Target = f32>> View for Oscilloscope<W> {
+    fn element(&self) -> Option<&'static str> {
+        Some("oscilloscope")
+    }
+
+    fn event(&mut self, cx: &mut EventContext, event: &mut Event) {
+        event.map(|window_event, meta| {
+            if let WindowEvent::MouseScroll(_, scroll_y) = window_event {
+                cx.emit(OscilloscopeEvent::Zoom(*scroll_y));
+                meta.consume();
+            }
+        });
+    }
+
+    fn draw(&self, cx: &mut DrawContext, canvas: &mut Canvas) {
+        let bounds = cx.bounds();
+        if bounds.w == 0.0 || bounds.h == 0.0 {
+            return;
+        }
+
+        let scope = &self.scope;
+        let sample_rate = scope.sample_rate.load(Ordering::Relaxed);
+        let window = ((self.window_ms.get(cx) / 1000.0 * sample_rate) as usize)
+            .clamp(MIN_WINDOW_SAMPLES, MAX_WINDOW_SAMPLES);
+        let lead = scope.oversampled_lead.load(Ordering::Relaxed);
+        let capture_oversampled = scope.capture_oversampled.load(Ordering::Relaxed);
+
+        // room for one window to search for a trigger and one to show, plus enough that the
+        // oversampled trace covers the same stretch of input as the others
+        let len = (2 * window + lead).min(SCOPE_CAPACITY);
+        let mut dry = vec![0.0; len];
+        let mut wet = vec![0.0; len];
+        scope.dry.snapshot(&mut dry);
+        scope.wet.snapshot(&mut wet);
+
+        let trigger = find_trigger(&dry[..len - lead], window);
+
+        let mut axis = vg::Path::new();
+        axis.move_to(bounds.x, bounds.y + bounds.h / 2.0);
+        axis.line_to(bounds.x + bounds.w, bounds.y + bounds.h / 2.0);
+        canvas.stroke_path(
+            &mut axis,
+            &vg::Paint::color(vg::Color::rgba(128, 128, 128, 96)).with_line_width(1.0),
+        );
+
+        // the theme sets the processed trace's color
+        let color = cx.font_color();
+        let wet_color = vg::Color::rgba(color.r(), color.g(), color.b(), color.a());
+        let oversampled_color = vg::Color::rgba(color.r(), color.g(), color.b(), color.a() / 2);
+
+        stroke_trace(
+            canvas,
+            bounds,
+            &dry[trigger..trigger + window],
+            &vg::Paint::color(vg::Color::rgba(128, 128, 128, 192)).with_line_width(1.5),
+        );
+        stroke_trace(
+            canvas,
+            bounds,
+            &wet[trigger..trigger + window],
+            &vg::Paint::color(wet_color).with_line_width(1.5),
+        );
+
+        if capture_oversampled {
+            // the base rate sample at `trigger` lines up with the start of this snapshot
+            let times = scope.oversampling_times.load(Ordering::Relaxed);
+            let oversampled_len = (len - trigger - lead) * times;
+            if oversampled_len <= SCOPE_CAPACITY {
+                let mut oversampled = vec![0.0; oversampled_len];
+                scope.oversampled.snapshot(&mut oversampled);
+                stroke_trace(
+                    canvas,
+                    bounds,
+                    &oversampled[..window * times],
+                    &vg::Paint::color(oversampled_color).with_line_width(1.0),
+                );
+            }
+        }
+    }
+}
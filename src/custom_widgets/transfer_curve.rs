@@ -0,0 +1,84 @@
+use nih_plug_vizia::vizia::prelude::*;
+use nih_plug_vizia::vizia::vg;
+use std::sync::Arc;
+
+use crate::chebyshev::ChebyshevShape;
+use crate::NonlinearAdaaParams;
+
+const NUM_POINTS: usize = 128;
+
+/// Plots the Chebyshev shaper's transfer curve over `-1..=1`. The shape is rebuilt from the
+/// parameters on every draw, so it follows automation as well as the editor's own controls.
+pub struct TransferCurve {
+    params: Arc<NonlinearAdaaParams>,
+}
+
+impl TransferCurve {
+    pub fn new<L>(cx: &mut Context, params: L) -> Handle<Self>
+    where
+        L: Lens<Target = Arc<NonlinearAdaaParams>>,
+    {
+        Self {
+            params: params.get(cx),
+        }
+        .build(cx, |_| {})
+    }
+}
+
+impl View for TransferCurve {
+    fn element(&self) -> Option<&'static str> {
+        Some("transfer-curve")
+    }
+
+    fn draw(&self, cx: &mut DrawContext, canvas: &mut Canvas) {
+        let bounds = cx.bounds();
+        if bounds.w == 0.0 || bounds.h == 0.0 {
+            return;
+        }
+
+        let shape: ChebyshevShape = self
+            .params
+            .nonlinearity
+            .chebyshev_shape(&self.params.midi_map);
+        let points: Vec<(f32, f32)> = (0..NUM_POINTS)
+            .map(|i| {
+                let x = -1.0 + 2.0 * i as f64 / (NUM_POINTS - 1) as f64;
+                (x as f32, shape.eval(x) as f32)
+            })
+            .collect();
+        // keep the whole curve in view when the harmonics push it past full scale
+        let y_scale = points.iter().fold(1.0_f32, |max, (_, y)| max.max(y.abs()));
+
+        let to_screen = |(x, y): (f32, f32)| {
+            (
+                bounds.x + (x + 1.0) / 2.0 * bounds.w,
+                bounds.y + (1.0 - y / y_scale) / 2.0 * bounds.h,
+            )
+        };
+
+        let mut axes = vg::Path::new();
+        axes.move_to(bounds.x, bounds.y + bounds.h / 2.0);
+        axes.line_to(bounds.x + bounds.w, bounds.y + bounds.h / 2.0);
+        axes.move_to(bounds.x + bounds.w / 2.0, bounds.y);
+        axes.line_to(bounds.x + bounds.w / 2.0, bounds.y + bounds.h);
+        canvas.stroke_path(
+            &mut axes,
+            &vg::Paint::color(vg::Color::rgba(128, 128, 128, 128)).with_line_width(1.0),
+        );
+
+        // the theme sets the curve's color
+        let color = cx.font_color();
+        let mut curve = vg::Path::new();
+        let (x0, y0) = to_screen(points[0]);
+        curve.move_to(x0, y0);
+        points.iter().skip(1).for_each(|point| {
+            let (x, y) = to_screen(*point);
+            curve.line_to(x, y);
+        });
+        canvas.stroke_path(
+            &mut curve,
+            &vg::Paint::color(vg::Color::rgba(color.r(), color.g(), color.b(), color.a()))
+                .with_line_width(2.0),
+        );
+    }
+}
@@ -0,0 +1,193 @@
+//! RC diode clipper: a series resistor into a capacitor shunted by a pair of antiparallel diodes.
+//!
+//! The capacitor voltage follows
+//!
+//! `dV/dt = (Vin - V) / (R C) - 2 Is / C * sinh(V / (n Vt))`
+//!
+//! which is discretised with the trapezoidal rule and solved for each sample with Newton–Raphson,
+//! capped at [`MAX_ITERATIONS`]. The capacitor makes the clipping frequency dependent: highs are
+//! rolled off before they reach the diodes. Input samples are treated as volts.
+
+use nih_plug::prelude::*;
+
+const THERMAL_VOLTAGE: f64 = 0.02585;
+const MAX_ITERATIONS: usize = 16;
+const TOLERANCE: f64 = 1e-9;
+// largest Newton step in volts, keeps the exponential from overflowing on a poor first guess
+const MAX_STEP: f64 = 0.25;
+
+/// Component values for the clipper. The diode models are approximate.
+#[derive(Enum, Debug, PartialEq, Eq, Clone, Copy)]
+pub enum DiodePreset {
+    #[id = "silicon"]
+    #[name = "Silicon (1N4148)"]
+    Silicon,
+    #[id = "germanium"]
+    #[name = "Germanium (1N34A)"]
+    Germanium,
+    #[id = "led"]
+    #[name = "Red LED"]
+    Led,
+    #[id = "silicon bright"]
+    #[name = "Silicon, Bright"]
+    SiliconBright,
+}
+
+struct Components {
+    resistance: f64,
+    capacitance: f64,
+    saturation_current: f64,
+    ideality: f64,
+}
+
+impl DiodePreset {
+    /// The most the clipper's output can reach for inputs up to `peak_input` volts. That much
+    /// input settles the capacitor where the diodes' forward current matches the current through
+    /// the resistor, and no input within it can charge the capacitor any further.
+    pub fn output_limit(self, peak_input: f32) -> f32 {
+        let components = self.components();
+        let diode_voltage = components.ideality * THERMAL_VOLTAGE;
+        let forward_voltage = (peak_input as f64
+            / (2.0 * components.resistance * components.saturation_current))
+            .asinh()
+            * diode_voltage;
+
+        (forward_voltage * components.output_scale()) as f32
+    }
+
+    fn components(self) -> Components {
+        match self {
+            DiodePreset::Silicon => Components {
+                resistance: 2.2e3,
+                capacitance: 10e-9,
+                saturation_current: 2.52e-9,
+                ideality: 1.752,
+            },
+            DiodePreset::Germanium => Components {
+                resistance: 2.2e3,
+                capacitance: 10e-9,
+                saturation_current: 2e-7,
+                ideality: 1.3,
+            },
+            DiodePreset::Led => Components {
+                resistance: 2.2e3,
+                capacitance: 10e-9,
+                saturation_current: 93.2e-12,
+                ideality: 3.73,
+            },
+            DiodePreset::SiliconBright => Components {
+                resistance: 1e3,
+                capacitance: 4.7e-9,
+                saturation_current: 2.52e-9,
+                ideality: 1.752,
+            },
+        }
+    }
+}
+
+impl Components {
+    /// Scales the capacitor voltage so a 1 V input sits around full scale: the DC voltage the
+    /// diodes settle at for a 1 V input is approximately `n Vt asinh(0.5 / (R Is))`.
+    fn output_scale(&self) -> f64 {
+        ((0.5 / (self.resistance * self.saturation_current)).asinh()
+            * self.ideality
+            * THERMAL_VOLTAGE)
+            .recip()
+    }
+}
+
+pub struct DiodeClipper {
+    preset: DiodePreset,
+    period: f64,
+    // 1 / RC
+    rc_recip: f64,
+    // 2 Is / C
+    diode_scale: f64,
+    // n Vt
+    diode_voltage: f64,
+    // scales the capacitor voltage so a 1 V input sits around full scale
+    output_scale: f64,
+    v: f64,
+    x_prev: f64,
+}
+
+impl Default for DiodeClipper {
+    fn default() -> Self {
+        let mut clipper = Self {
+            preset: DiodePreset::Silicon,
+            period: 1.0 / 88200.0,
+            rc_recip: 0.0,
+            diode_scale: 0.0,
+            diode_voltage: 1.0,
+            output_scale: 1.0,
+            v: 0.0,
+            x_prev: 0.0,
+        };
+        clipper.update_components();
+        clipper
+    }
+}
+
+impl DiodeClipper {
+    pub fn set_sample_rate(&mut self, sample_rate: f32) {
+        self.period = (sample_rate as f64).recip();
+    }
+
+    pub fn set_preset(&mut self, preset: DiodePreset) {
+        if preset != self.preset {
+            self.preset = preset;
+            self.update_components();
+        }
+    }
+
+    fn update_components(&mut self) {
+        let components = self.preset.components();
+
+        self.rc_recip = (components.resistance * components.capacitance).recip();
+        self.diode_scale = 2.0 * components.saturation_current / components.capacitance;
+        self.diode_voltage = components.ideality * THERMAL_VOLTAGE;
+        self.output_scale = components.output_scale();
+    }
+
+    /// dV/dt and its derivative with respect to V.
+    #[inline]
+    fn slope(&self, v: f64, x: f64) -> (f64, f64) {
+        let arg = v / self.diode_voltage;
+        let f = (x - v) * self.rc_recip - self.diode_scale * arg.sinh();
+        let df = -self.rc_recip - self.diode_scale / self.diode_voltage * arg.cosh();
+        (f, df)
+    }
+
+    #[inline]
+    pub fn process(&mut self, x: f32) -> f32 {
+        let x = x as f64;
+        let half_period = 0.5 * self.period;
+        let (f_prev, _) = self.slope(self.v, self.x_prev);
+
+        let mut v = self.v;
+        for _ in 0..MAX_ITERATIONS {
+            let (f, df) = self.slope(v, x);
+            let residual = v - self.v - half_period * (f + f_prev);
+            let step = (residual / (1.0 - half_period * df)).clamp(-MAX_STEP, MAX_STEP);
+            v -= step;
+
+            if step.abs() < TOLERANCE {
+                break;
+            }
+        }
+
+        if v.is_finite() {
+            self.v = v;
+            self.x_prev = x;
+        } else {
+            self.reset();
+        }
+
+        (self.v * self.output_scale) as f32
+    }
+
+    pub fn reset(&mut self) {
+        self.v = 0.0;
+        self.x_prev = 0.0;
+    }
+}
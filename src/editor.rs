@@ -1,27 +1,344 @@
 use crate::AtomicF32;
-use nih_plug::prelude::{util, Editor};
+use nih_plug::prelude::{nih_log, util, AsyncExecutor, Editor, GuiContext, Param, ParamPtr};
 use nih_plug_vizia::vizia::prelude::*;
 use nih_plug_vizia::widgets::*;
 use nih_plug_vizia::{assets, create_vizia_editor, ViziaState, ViziaTheming};
+use std::collections::BTreeSet;
+use std::path::{Path, PathBuf};
 use std::sync::atomic::Ordering;
-use std::sync::Arc;
+use std::sync::{Arc, Weak};
+use std::time::Duration;
 
-use crate::NonlinearAdaaParams;
+use crate::analysis;
+use crate::analysis::{AnalysisMode, HarmonicAnalysis, ANALYSIS_HARMONICS};
+use crate::cabinet;
+use crate::chebyshev::{HarmonicParams, NUM_HARMONICS};
+use crate::custom_widgets::harmonic_chart::HarmonicChart;
+use crate::custom_widgets::midi_learn::{MidiLearn, MidiLearnEvent};
+use crate::custom_widgets::oscilloscope::{Oscilloscope, OscilloscopeEvent};
+use crate::custom_widgets::transfer_curve::TransferCurve;
+use crate::midi;
+use crate::randomize::{self, RandomizeTarget, Rng};
+use crate::scope::Scope;
+use crate::stages::StageParams;
+use crate::theme::{self, EditorTheme};
+use crate::undo::UndoHistory;
+use crate::{NonlinearAdaa, NonlinearAdaaParams, Task};
 
-// use crate::custom_widgets::knob;
+use crate::custom_widgets::knob::ParamKnob;
 
 #[derive(Lens)]
 struct Data {
     params: Arc<NonlinearAdaaParams>,
     input_peak_meters: [Arc<AtomicF32>; 2],
     output_peak_meters: [Arc<AtomicF32>; 2],
+    gate_meter: Arc<AtomicF32>,
+    scope: Arc<Scope>,
+    scope_window_ms: f32,
+    scope_oversampled: bool,
+    async_executor: AsyncExecutor<NonlinearAdaa>,
+    ir_path_input: String,
+    // file name of the loaded impulse response, as shown above the path input
+    ir_name: String,
+    ui_scale: f64,
+    theme: EditorTheme,
+    // whether the user stylesheet is one of the editor's stylesheets yet, it may only be created
+    // while the editor is open
+    user_stylesheet_added: bool,
+    analysis_mode: AnalysisMode,
+    analysis: Option<HarmonicAnalysis>,
+    oversampled_nyquist: f32,
+    // the threads the editor starts run for as long as this is alive
+    alive: Arc<()>,
+    gui_context: Arc<dyn GuiContext>,
+    history: UndoHistory,
+    midi_learning: Option<ParamPtr>,
+    // the parameter last right clicked, whose CC the status shows
+    midi_param: Option<ParamPtr>,
+    midi_status: String,
+    randomize_locks: BTreeSet<RandomizeTarget>,
 }
 
-impl Model for Data {}
+enum UndoEvent {
+    Undo,
+    Redo,
+}
+
+enum RandomizeEvent {
+    Randomize,
+    ToggleLock(RandomizeTarget),
+}
+
+enum MidiEvent {
+    /// CCs have moved parameters or changed assignments.
+    Sync,
+}
+
+enum CabinetEvent {
+    SetPath(String),
+    Load,
+    Clear,
+    /// A background load or clear finished with this impulse response loaded.
+    Loaded(Option<String>),
+}
+
+/// Sets the user scale factor of the whole interface.
+enum ScaleEvent {
+    Set(f64),
+}
+
+enum ScopeEvent {
+    ToggleOversampled,
+}
+
+const SCOPE_WINDOW_MS_RANGE: (f32, f32) = (1.0, 200.0);
+// each scroll line or zoom button press scales the window by this much
+const SCOPE_ZOOM_STEP: f32 = 1.25;
+
+enum AnalysisEvent {
+    SetMode(AnalysisMode),
+    Update(Option<HarmonicAnalysis>, f32),
+}
+
+enum ThemeEvent {
+    Set(EditorTheme),
+    /// The user stylesheet at this path was created or changed.
+    ReloadUserStylesheet(PathBuf),
+}
+
+/// Scale presets offered next to the resize handle.
+const UI_SCALES: [f64; 6] = [0.75, 1.0, 1.25, 1.5, 1.75, 2.0];
+
+impl Data {
+    /// Sets a parameter from the undo history as one gesture.
+    fn restore(&self, (param, normalized): (ParamPtr, f32)) {
+        // SAFETY: the history only holds pointers to `params`, which outlive the editor
+        unsafe {
+            self.gui_context.raw_begin_set_parameter(param);
+            self.gui_context
+                .raw_set_parameter_normalized(param, normalized);
+            self.gui_context.raw_end_set_parameter(param);
+        }
+    }
+
+    /// Moves every unlocked randomizer target to a new value, as one undo step.
+    fn randomize(&mut self) {
+        let changes =
+            randomize::random_changes(&self.params, &self.randomize_locks, &mut Rng::from_time());
+
+        for change in &changes {
+            self.restore((change.param, change.after));
+        }
+        self.history.record(changes);
+    }
+
+    /// Passes the values CCs have set on to the host and updates the MIDI learn status.
+    fn sync_midi(&mut self) {
+        let params = self.params.clone();
+        params
+            .midi_map
+            .drain_pending(|param, value| self.restore((param, value)));
+
+        self.midi_learning = params.midi_map.learning();
+        // SAFETY: the MIDI map only holds pointers to `params`
+        self.midi_status = match (self.midi_learning, self.midi_param) {
+            (Some(param), _) => {
+                format!("MIDI learn: move a controller for {}", unsafe {
+                    param.name()
+                })
+            }
+            (None, Some(param)) => match params.midi_map.assigned_cc(param) {
+                Some(cc) => format!("{} on CC {cc}", unsafe { param.name() }),
+                None => format!("{} has no CC", unsafe { param.name() }),
+            },
+            (None, None) => String::new(),
+        };
+    }
+}
+
+impl Model for Data {
+    fn event(&mut self, cx: &mut EventContext, event: &mut Event) {
+        // every edit made by a parameter widget passes through here on its way to the host
+        event.map(|param_event, _| match param_event {
+            RawParamEvent::BeginSetParameter(param) => {
+                // SAFETY: widgets only send pointers to `params`
+                let value = unsafe { param.unmodulated_normalized_value() };
+                self.history.begin(*param, value);
+            }
+            RawParamEvent::SetParameterNormalized(param, value) => self.history.set(*param, *value),
+            RawParamEvent::EndSetParameter(param) => self.history.end(*param),
+            _ => {}
+        });
+
+        event.map(|undo_event, _| {
+            let step = match undo_event {
+                UndoEvent::Undo => self.history.undo(),
+                UndoEvent::Redo => self.history.redo(),
+            };
+            if let Some(step) = step {
+                step.into_iter().for_each(|change| self.restore(change));
+            }
+        });
+
+        event.map(|randomize_event, _| match randomize_event {
+            RandomizeEvent::Randomize => self.randomize(),
+            RandomizeEvent::ToggleLock(target) => {
+                if !self.randomize_locks.remove(target) {
+                    self.randomize_locks.insert(*target);
+                }
+                *self.params.randomize_locks.write().unwrap() = self.randomize_locks.clone();
+            }
+        });
+
+        event.map(|window_event, meta| {
+            if let WindowEvent::KeyDown(code, _) = window_event {
+                let modifiers = cx.modifiers();
+                if modifiers.command() {
+                    match code {
+                        Code::KeyZ if modifiers.shift() => cx.emit(UndoEvent::Redo),
+                        Code::KeyZ => cx.emit(UndoEvent::Undo),
+                        Code::KeyY => cx.emit(UndoEvent::Redo),
+                        _ => return,
+                    }
+                    meta.consume();
+                } else if *code == Code::Escape && self.midi_learning.is_some() {
+                    self.params.midi_map.cancel_learn();
+                    self.sync_midi();
+                    meta.consume();
+                }
+            }
+        });
+
+        event.map(|midi_learn_event, _| match midi_learn_event {
+            MidiLearnEvent::Toggle(param) => {
+                let midi_map = &self.params.midi_map;
+                if midi_map.learning() == Some(*param) {
+                    // a second right click while waiting clears the parameter's CC instead
+                    midi_map.cancel_learn();
+                    midi_map.forget(*param);
+                } else {
+                    midi_map.learn(*param);
+                }
+                self.midi_param = Some(*param);
+                self.sync_midi();
+            }
+        });
+
+        event.map(|midi_event, _| match midi_event {
+            MidiEvent::Sync => self.sync_midi(),
+        });
+
+        event.map(|cabinet_event, _| match cabinet_event {
+            CabinetEvent::SetPath(path) => self.ir_path_input = path.clone(),
+            CabinetEvent::Load => {
+                if !self.ir_path_input.is_empty() {
+                    self.async_executor
+                        .execute_background(Task::LoadImpulseResponse(
+                            self.ir_path_input.trim().into(),
+                        ));
+                }
+            }
+            CabinetEvent::Clear => self
+                .async_executor
+                .execute_background(Task::ClearImpulseResponse),
+            CabinetEvent::Loaded(path) => {
+                self.ir_name = ir_name(path.as_deref());
+                self.ir_path_input = path.clone().unwrap_or_default();
+            }
+        });
+
+        event.map(|scale_event, _| match scale_event {
+            ScaleEvent::Set(scale) => {
+                self.ui_scale = *scale;
+                // the window follows the new scale at the start of the next frame and the editor
+                // state stores it with the project
+                cx.set_user_scale_factor(*scale);
+                cx.emit(GuiContextEvent::Resize);
+            }
+        });
+
+        // dragging the resize handle changes the scale too, keep the presets' checked state in
+        // step with it
+        event.map(|gui_context_event, _| {
+            if let GuiContextEvent::Resize = gui_context_event {
+                self.ui_scale = cx.user_scale_factor();
+            }
+        });
+
+        event.map(|scope_event, _| match scope_event {
+            ScopeEvent::ToggleOversampled => {
+                self.scope_oversampled = !self.scope_oversampled;
+                self.scope
+                    .capture_oversampled
+                    .store(self.scope_oversampled, Ordering::Relaxed);
+            }
+        });
+
+        event.map(|oscilloscope_event, _| match oscilloscope_event {
+            OscilloscopeEvent::Zoom(lines) => {
+                self.scope_window_ms = (self.scope_window_ms / SCOPE_ZOOM_STEP.powf(*lines))
+                    .clamp(SCOPE_WINDOW_MS_RANGE.0, SCOPE_WINDOW_MS_RANGE.1);
+            }
+        });
+
+        event.map(|analysis_event, _| match analysis_event {
+            AnalysisEvent::SetMode(mode) => {
+                self.analysis_mode = *mode;
+                self.params.analysis_mode.set(*mode);
+            }
+            AnalysisEvent::Update(analysis, oversampled_nyquist) => {
+                self.analysis = *analysis;
+                self.oversampled_nyquist = *oversampled_nyquist;
+            }
+        });
+
+        event.map(|theme_event, _| match theme_event {
+            ThemeEvent::Set(theme) => {
+                self.theme = *theme;
+                *self.params.editor_theme.write().unwrap() = *theme;
+            }
+            ThemeEvent::ReloadUserStylesheet(path) => {
+                if self.user_stylesheet_added {
+                    if let Err(err) = cx.reload_styles() {
+                        nih_log!("Could not reload the user stylesheet: {err}");
+                    }
+                } else {
+                    match cx.add_stylesheet(path) {
+                        Ok(()) => self.user_stylesheet_added = true,
+                        Err(err) => nih_log!("Could not load {}: {err}", path.display()),
+                    }
+                }
+            }
+        });
+    }
+}
 
 // Makes sense to also define this here, makes it a bit easier to keep track of
 pub(crate) fn default_state() -> Arc<ViziaState> {
-    ViziaState::new(|| (430, 600))
+    ViziaState::new(|| (1300, 700))
+}
+
+/// Calls `poll` on a background thread every `interval` and emits the events it returns, until
+/// `alive` can no longer be upgraded because the editor has closed.
+pub(crate) fn poll_while_open<E>(
+    cx: &mut Context,
+    alive: Weak<()>,
+    interval: Duration,
+    mut poll: impl FnMut() -> Option<E> + Send + 'static,
+) where
+    E: Send + 'static,
+{
+    cx.spawn(move |proxy| {
+        while alive.upgrade().is_some() {
+            std::thread::sleep(interval);
+
+            if let Some(event) = poll() {
+                if proxy.emit(event).is_err() {
+                    break;
+                }
+            }
+        }
+    });
 }
 
 #[derive(Debug)]
@@ -33,145 +350,715 @@ pub(crate) fn create(
     params: Arc<NonlinearAdaaParams>,
     input_meters: [Arc<AtomicF32>; 2],
     output_meters: [Arc<AtomicF32>; 2],
+    gate_meter: Arc<AtomicF32>,
+    scope: Arc<Scope>,
     editor_state: Arc<ViziaState>,
+    async_executor: AsyncExecutor<NonlinearAdaa>,
 ) -> Option<Box<dyn Editor>> {
-    create_vizia_editor(editor_state, ViziaTheming::Custom, move |cx, _| {
-        assets::register_noto_sans_light(cx);
-        assets::register_noto_sans_thin(cx);
-        assets::register_noto_sans_bold(cx);
-
-        Data {
-            params: params.clone(),
-            input_peak_meters: input_meters.clone(),
-            output_peak_meters: output_meters.clone(),
+    create_vizia_editor(
+        editor_state.clone(),
+        ViziaTheming::Custom,
+        move |cx, gui_context| {
+            assets::register_noto_sans_light(cx);
+            assets::register_noto_sans_thin(cx);
+            assets::register_noto_sans_bold(cx);
+
+            cx.add_theme(theme::THEME_CSS);
+            let alive = Arc::new(());
+            let mut user_stylesheet_added = false;
+            if let Some(path) = theme::user_stylesheet_path() {
+                if path.exists() {
+                    match cx.add_stylesheet(&path) {
+                        Ok(()) => user_stylesheet_added = true,
+                        Err(err) => nih_log!("Could not load {}: {err}", path.display()),
+                    }
+                }
+                // watched even if it does not exist yet, so it applies as soon as it is created
+                let watched = path.clone();
+                theme::watch_user_stylesheet(cx, path, Arc::downgrade(&alive), move || {
+                    ThemeEvent::ReloadUserStylesheet(watched.clone())
+                });
+            }
+            analysis::spawn_analyzer(
+                cx,
+                scope.clone(),
+                params.clone(),
+                Arc::downgrade(&alive),
+                AnalysisEvent::Update,
+            );
+            midi::watch_cc_map(cx, params.clone(), Arc::downgrade(&alive), || {
+                MidiEvent::Sync
+            });
+            randomize::watch_trigger(cx, params.clone(), Arc::downgrade(&alive), || {
+                RandomizeEvent::Randomize
+            });
+            cabinet::watch_ir_path(
+                cx,
+                params.clone(),
+                Arc::downgrade(&alive),
+                CabinetEvent::Loaded,
+            );
+
+            Data {
+                params: params.clone(),
+                input_peak_meters: input_meters.clone(),
+                output_peak_meters: output_meters.clone(),
+                gate_meter: gate_meter.clone(),
+                scope: scope.clone(),
+                scope_window_ms: 20.0,
+                scope_oversampled: scope.capture_oversampled.load(Ordering::Relaxed),
+                async_executor: async_executor.clone(),
+                ir_path_input: params.ir_path.read().unwrap().clone().unwrap_or_default(),
+                ir_name: ir_name(params.ir_path.read().unwrap().as_deref()),
+                ui_scale: editor_state.user_scale_factor(),
+                theme: *params.editor_theme.read().unwrap(),
+                user_stylesheet_added,
+                analysis_mode: params.analysis_mode.get(),
+                analysis: None,
+                oversampled_nyquist: 0.0,
+                alive,
+                gui_context,
+                history: UndoHistory::default(),
+                midi_learning: params.midi_map.learning(),
+                midi_param: None,
+                midi_status: String::new(),
+                randomize_locks: params.randomize_locks.read().unwrap().clone(),
+            }
+            .build(cx);
+
+            VStack::new(cx, |cx| {
+                HStack::new(cx, |cx| {
+                    Label::new(cx, "Nonlinear ADAA").font_size(30.0);
+                    undo_controls(cx);
+                    randomize_controls(cx);
+                    button(cx, |params| &params.bypass);
+                    theme_controls(cx);
+                    scale_controls(cx);
+                    Label::new(cx, Data::midi_status);
+                })
+                .child_space(Stretch(1.0))
+                .col_between(Pixels(20.0))
+                .height(Pixels(50.0));
+
+                HStack::new(cx, |cx| {
+                    VStack::new(cx, |cx| {
+                        ScrollView::new(cx, 0.0, 0.0, false, true, |cx| {
+                            HStack::new(cx, |cx| {
+                                section(cx, "Input", input_section);
+                                section(cx, "Nonlinearity", nonlinearity_section);
+                                section(cx, "Filter", filter_section);
+                                section(cx, "Output", output_section);
+                            })
+                            .col_between(Pixels(15.0))
+                            .height(Auto);
+                        })
+                        .child_space(Pixels(10.0));
+
+                        HStack::new(cx, |cx| {
+                            scope_pane(cx);
+                            analysis_pane(cx);
+                        })
+                        .col_between(Pixels(10.0))
+                        .height(Pixels(170.0));
+                    })
+                    .row_between(Pixels(10.0));
+
+                    meters(cx);
+                })
+                .col_between(Pixels(10.0));
+            })
+            .row_between(Pixels(10.0))
+            .toggle_class(
+                EditorTheme::Dark.class(),
+                Data::theme.map(|theme| *theme == EditorTheme::Dark),
+            )
+            .toggle_class(
+                EditorTheme::Light.class(),
+                Data::theme.map(|theme| *theme == EditorTheme::Light),
+            );
+
+            ResizeHandle::new(cx);
+        },
+    )
+}
+
+/// Undo and redo for edits made in the editor, also on command/ctrl+Z and command/ctrl+shift+Z
+/// or command/ctrl+Y.
+fn undo_controls(cx: &mut Context) {
+    HStack::new(cx, |cx| {
+        Button::new(
+            cx,
+            |cx| cx.emit(UndoEvent::Undo),
+            |cx| Label::new(cx, "Undo"),
+        )
+        .disabled(Data::history.map(|history| !history.can_undo()));
+        Button::new(
+            cx,
+            |cx| cx.emit(UndoEvent::Redo),
+            |cx| Label::new(cx, "Redo"),
+        )
+        .disabled(Data::history.map(|history| !history.can_redo()));
+    })
+    .col_between(Pixels(4.0))
+    .width(Auto)
+    .height(Auto);
+}
+
+/// Randomizes the main controls, skipping the locked ones.
+fn randomize_controls(cx: &mut Context) {
+    HStack::new(cx, |cx| {
+        Button::new(
+            cx,
+            |cx| cx.emit(RandomizeEvent::Randomize),
+            |cx| Label::new(cx, "Randomize"),
+        );
+        Label::new(cx, "Lock");
+        for target in RandomizeTarget::ALL {
+            Button::new(
+                cx,
+                move |cx| cx.emit(RandomizeEvent::ToggleLock(target)),
+                move |cx| Label::new(cx, target.name()),
+            )
+            .checked(Data::randomize_locks.map(move |locks| locks.contains(&target)));
         }
-        .build(cx);
+    })
+    .col_between(Pixels(4.0))
+    .width(Auto)
+    .height(Auto);
+}
 
-        VStack::new(cx, |cx| {
-            // Knob::new(cx, 0.5, , false);
+/// Selects one of the built-in themes. A user stylesheet, if there is one, applies on top of
+/// either.
+fn theme_controls(cx: &mut Context) {
+    HStack::new(cx, |cx| {
+        for editor_theme in EditorTheme::ALL {
+            Button::new(
+                cx,
+                move |cx| cx.emit(ThemeEvent::Set(editor_theme)),
+                move |cx| Label::new(cx, editor_theme.name()),
+            )
+            .checked(Data::theme.map(move |theme| *theme == editor_theme));
+        }
+    })
+    .col_between(Pixels(4.0))
+    .width(Auto)
+    .height(Auto);
+}
 
+/// Buttons for the interface scale presets. The resize handle in the corner scales freely.
+fn scale_controls(cx: &mut Context) {
+    HStack::new(cx, |cx| {
+        Label::new(cx, "Scale");
+        for scale in UI_SCALES {
+            Button::new(
+                cx,
+                move |cx| cx.emit(ScaleEvent::Set(scale)),
+                move |cx| Label::new(cx, &format!("{:.0}%", scale * 100.0)),
+            )
+            .checked(Data::ui_scale.map(move |ui_scale| (ui_scale - scale).abs() < 1e-3));
+        }
+    })
+    .col_between(Pixels(4.0))
+    .width(Auto)
+    .height(Auto);
+}
+
+/// A titled column of controls.
+fn section(cx: &mut Context, title: &str, content: fn(&mut Context)) {
+    VStack::new(cx, |cx| {
+        Label::new(cx, title).font_size(20.0);
+        content(cx);
+    })
+    .class("section")
+    .width(Pixels(260.0))
+    .height(Auto)
+    .row_between(Pixels(10.0));
+}
+
+/// Knobs laid out side by side. Sections fit three to a row.
+fn knob_row(cx: &mut Context, content: impl FnOnce(&mut Context)) {
+    HStack::new(cx, content)
+        .col_between(Pixels(5.0))
+        .height(Auto);
+}
+
+/// Wraps a control in MIDI learn for its parameter, outlined while it waits for a controller.
+fn midi_learn<P, FMap>(
+    cx: &mut Context,
+    params_to_param: FMap,
+    content: impl FnOnce(&mut Context),
+) -> Handle<MidiLearn>
+where
+    P: Param + 'static,
+    FMap: Fn(&Arc<NonlinearAdaaParams>) -> &P + Copy + 'static,
+{
+    let params = Data::params.get(cx);
+    let param = params_to_param(&params).as_ptr();
+    MidiLearn::new(cx, param, content).toggle_class(
+        "midi-learning",
+        Data::midi_learning.map(move |learning| *learning == Some(param)),
+    )
+}
+
+fn knob<P, FMap>(cx: &mut Context, params_to_param: FMap) -> Handle<MidiLearn>
+where
+    P: Param + 'static,
+    FMap: Fn(&Arc<NonlinearAdaaParams>) -> &P + Copy + 'static,
+{
+    midi_learn(cx, params_to_param, |cx| {
+        ParamKnob::new(cx, Data::params, params_to_param);
+    })
+}
+
+fn slider<P, FMap>(cx: &mut Context, params_to_param: FMap) -> Handle<MidiLearn>
+where
+    P: Param + 'static,
+    FMap: Fn(&Arc<NonlinearAdaaParams>) -> &P + Copy + 'static,
+{
+    midi_learn(cx, params_to_param, |cx| {
+        ParamSlider::new(cx, Data::params, params_to_param);
+    })
+}
+
+fn button<P, FMap>(cx: &mut Context, params_to_param: FMap) -> Handle<MidiLearn>
+where
+    P: Param + 'static,
+    FMap: Fn(&Arc<NonlinearAdaaParams>) -> &P + Copy + 'static,
+{
+    midi_learn(cx, params_to_param, |cx| {
+        ParamButton::new(cx, Data::params, params_to_param);
+    })
+}
+
+fn input_section(cx: &mut Context) {
+    knob_row(cx, |cx| {
+        knob(cx, |params| &params.input.gain);
+    });
+
+    gate_controls(cx);
+}
+
+fn nonlinearity_section(cx: &mut Context) {
+    VStack::new(cx, |cx| {
+        Label::new(cx, "Clip Style");
+        slider(cx, |params| &params.nonlinearity.nl_proc_type);
+        Label::new(cx, "Clip Style B");
+        slider(cx, |params| &params.nonlinearity.nl_proc_type_b);
+        Label::new(cx, "ADAA Order");
+        slider(cx, |params| &params.nonlinearity.nl_proc_order);
+        Label::new(cx, "Oversampling");
+        slider(cx, |params| &params.nonlinearity.os_level);
+        Label::new(cx, "Stages");
+        slider(cx, |params| &params.nonlinearity.num_stages);
+    })
+    .height(Auto)
+    .row_between(Pixels(2.0));
+
+    knob_row(cx, |cx| {
+        knob(cx, |params| &params.nonlinearity.morph);
+        knob(cx, |params| &params.nonlinearity.feedback);
+    });
+
+    hysteresis_controls(cx);
+
+    VStack::new(cx, |cx| {
+        Label::new(cx, "Diode Clipper").font_size(16.0);
+        slider(cx, |params| &params.nonlinearity.diode_preset);
+    })
+    .height(Auto)
+    .row_between(Pixels(2.0));
+
+    chebyshev_controls(cx);
+
+    stage_controls(cx, "Stage 2", |params| &params.nonlinearity.stage_2);
+    stage_controls(cx, "Stage 3", |params| &params.nonlinearity.stage_3);
+    stage_controls(cx, "Stage 4", |params| &params.nonlinearity.stage_4);
+}
+
+fn filter_section(cx: &mut Context) {
+    knob_row(cx, |cx| {
+        knob(cx, |params| &params.filter.pre_filter_cutoff);
+    });
+
+    emphasis_controls(cx);
+
+    feedback_controls(cx);
+
+    stage_filter_controls(cx, "Stage 2", |params| &params.nonlinearity.stage_2);
+    stage_filter_controls(cx, "Stage 3", |params| &params.nonlinearity.stage_3);
+    stage_filter_controls(cx, "Stage 4", |params| &params.nonlinearity.stage_4);
+}
+
+fn output_section(cx: &mut Context) {
+    knob_row(cx, |cx| {
+        knob(cx, |params| &params.output.gain);
+        knob(cx, |params| &params.output.dry_wet);
+    });
+
+    cabinet_controls(cx);
+
+    ceiling_controls(cx);
+}
+
+/// Triggered oscilloscope of the dry and processed signals. Scroll over it to zoom.
+fn scope_pane(cx: &mut Context) {
+    HStack::new(cx, |cx| {
+        VStack::new(cx, |cx| {
+            Label::new(cx, "Scope").font_size(16.0);
+            Label::new(
+                cx,
+                Data::scope_window_ms.map(|window_ms| format!("{window_ms:.1} ms")),
+            );
             HStack::new(cx, |cx| {
-                Label::new(cx, "Nonlinear ADAA").font_size(30.0);
+                Button::new(
+                    cx,
+                    |cx| cx.emit(OscilloscopeEvent::Zoom(-1.0)),
+                    |cx| Label::new(cx, "-"),
+                );
+                Button::new(
+                    cx,
+                    |cx| cx.emit(OscilloscopeEvent::Zoom(1.0)),
+                    |cx| Label::new(cx, "+"),
+                );
             })
-            .child_space(Stretch(1.0))
-            //.border_width(Pixels(5.0))
-            //.border_color(Color::black())
-            .height(Pixels(50.0));
+            .col_between(Pixels(4.0))
+            .height(Auto);
+            Button::new(
+                cx,
+                |cx| cx.emit(ScopeEvent::ToggleOversampled),
+                |cx| Label::new(cx, "Oversampled"),
+            )
+            .checked(Data::scope_oversampled);
+        })
+        .width(Pixels(110.0))
+        .row_between(Pixels(4.0));
+
+        Oscilloscope::new(cx, Data::scope, Data::scope_window_ms)
+            .width(Stretch(1.0))
+            .height(Stretch(1.0));
+    })
+    .class("section")
+    .col_between(Pixels(10.0));
+}
+
+/// A line of the distortion readout, blank while there is nothing to analyse.
+fn analysis_readout(cx: &mut Context, format: fn(&HarmonicAnalysis) -> String) {
+    Label::new(
+        cx,
+        Data::analysis.map(move |analysis| analysis.as_ref().map(format).unwrap_or_default()),
+    );
+}
 
+/// Buttons for the analysis modes. The mode is not saved and the tone is only injected while
+/// the editor is open.
+fn analysis_mode_controls(cx: &mut Context) {
+    HStack::new(cx, |cx| {
+        for mode in AnalysisMode::ALL {
+            Button::new(
+                cx,
+                move |cx| cx.emit(AnalysisEvent::SetMode(mode)),
+                move |cx| Label::new(cx, mode.name()),
+            )
+            .checked(Data::analysis_mode.map(move |analysis_mode| *analysis_mode == mode));
+        }
+    })
+    .col_between(Pixels(4.0))
+    .width(Auto)
+    .height(Auto);
+}
+
+/// Distortion of the processed signal while an analysis mode is on: the readout, and a chart of
+/// harmonics 2 to 10 with the level of everything off the harmonics marked by a line.
+fn analysis_pane(cx: &mut Context) {
+    HStack::new(cx, |cx| {
+        VStack::new(cx, |cx| {
+            Label::new(cx, "Analysis").font_size(16.0);
+            analysis_mode_controls(cx);
+            knob_row(cx, |cx| {
+                knob(cx, |params| &params.input.analysis_frequency);
+                knob(cx, |params| &params.input.analysis_level);
+            });
+        })
+        .width(Pixels(170.0))
+        .row_between(Pixels(4.0));
+
+        VStack::new(cx, |cx| {
+            analysis_readout(cx, |analysis| {
+                format!("Tone: {:.0} Hz", analysis.fundamental_hz)
+            });
+            analysis_readout(cx, |analysis| {
+                format!(
+                    "THD: {:.1} dB ({:.2} %)",
+                    analysis.thd_db,
+                    100.0 * 10f64.powf(analysis.thd_db / 20.0)
+                )
+            });
+            analysis_readout(cx, |analysis| format!("THD+N: {:.1} dB", analysis.thd_n_db));
+            analysis_readout(cx, |analysis| {
+                format!("Aliasing: {:.1} dB", analysis.alias_db)
+            });
+            Label::new(
+                cx,
+                Data::oversampled_nyquist
+                    .map(|nyquist| format!("Folds back above {:.1} kHz", nyquist / 1000.0)),
+            )
+            .display(Data::analysis.map(|analysis| analysis.is_some()));
+        })
+        .width(Pixels(170.0))
+        .row_between(Pixels(2.0));
+
+        VStack::new(cx, |cx| {
+            HarmonicChart::new(cx, Data::analysis)
+                .width(Stretch(1.0))
+                .height(Stretch(1.0));
             HStack::new(cx, |cx| {
-                VStack::new(cx, |cx| {
-                    VStack::new(cx, |cx| {
-                        ParamButton::new(cx, Data::params, |params| &params.bypass);
+                for harmonic in ANALYSIS_HARMONICS {
+                    Label::new(cx, &harmonic.to_string())
+                        .width(Stretch(1.0))
+                        .child_left(Stretch(1.0))
+                        .child_right(Stretch(1.0));
+                }
+            })
+            .height(Auto);
+        })
+        .width(Pixels(200.0));
+    })
+    .class("section")
+    .col_between(Pixels(10.0))
+    .width(Auto);
+}
 
-                        VStack::new(cx, |cx| {
-                            Label::new(cx, "Gain");
-                            ParamSlider::new(cx, Data::params, |params| &params.gain);
-                        })
-                        .row_between(Pixels(2.0));
+/// Input, output and gate level meters.
+fn meters(cx: &mut Context) {
+    VStack::new(cx, |cx| {
+        VStack::new(cx, |cx| {
+            Label::new(cx, "Input Level");
+            PeakMeter::new(
+                cx,
+                Data::input_peak_meters
+                    .map(|peak_meter| util::gain_to_db(peak_meter[0].load(Ordering::Relaxed))),
+                Some(Duration::from_millis(600)),
+            );
+            PeakMeter::new(
+                cx,
+                Data::input_peak_meters
+                    .map(|peak_meter| util::gain_to_db(peak_meter[1].load(Ordering::Relaxed))),
+                Some(Duration::from_millis(600)),
+            );
+        })
+        .height(Auto)
+        .row_between(Pixels(5.0));
 
-                        VStack::new(cx, |cx| {
-                            Label::new(cx, "Output");
-                            ParamSlider::new(cx, Data::params, |params| &params.output);
-                        })
-                        .row_between(Pixels(2.0));
+        VStack::new(cx, |cx| {
+            Label::new(cx, "Output Level");
+            PeakMeter::new(
+                cx,
+                Data::output_peak_meters
+                    .map(|peak_meter| util::gain_to_db(peak_meter[0].load(Ordering::Relaxed))),
+                Some(Duration::from_millis(600)),
+            );
+            PeakMeter::new(
+                cx,
+                Data::output_peak_meters
+                    .map(|peak_meter| util::gain_to_db(peak_meter[1].load(Ordering::Relaxed))),
+                Some(Duration::from_millis(600)),
+            );
+        })
+        .height(Auto)
+        .row_between(Pixels(5.0));
 
-                        VStack::new(cx, |cx| {
-                            Label::new(cx, "Prefilter Cutoff Frequency");
-                            ParamSlider::new(cx, Data::params, |params| &params.pre_filter_cutoff);
-                        })
-                        .row_between(Pixels(2.0));
+        VStack::new(cx, |cx| {
+            Label::new(cx, "Gate");
+            PeakMeter::new(
+                cx,
+                Data::gate_meter.map(|gate| util::gain_to_db(gate.load(Ordering::Relaxed))),
+                None,
+            );
+        })
+        .height(Auto)
+        .row_between(Pixels(5.0));
+    })
+    .width(Pixels(200.0))
+    .child_space(Pixels(10.0))
+    .row_between(Pixels(10.0));
+}
 
-                        VStack::new(cx, |cx| {
-                            Label::new(cx, "Clip Style");
-                            ParamSlider::new(cx, Data::params, |params| &params.nl_proc_type);
-                        })
-                        .row_between(Pixels(2.0));
+/// Controls for one of the gain stages that follow the main nonlinearity. Its filters live in the
+/// filter section.
+fn stage_controls(cx: &mut Context, label: &str, stage: fn(&NonlinearAdaaParams) -> &StageParams) {
+    VStack::new(cx, |cx| {
+        Label::new(cx, label).font_size(16.0);
+        slider(cx, move |params| &stage(params).style);
+        slider(cx, move |params| &stage(params).order);
+        knob_row(cx, |cx| {
+            knob(cx, move |params| &stage(params).drive);
+        });
+    })
+    .height(Auto)
+    .row_between(Pixels(2.0));
+}
 
-                        VStack::new(cx, |cx| {
-                            Label::new(cx, "ADAA order");
-                            ParamSlider::new(cx, Data::params, |params| &params.nl_proc_order);
-                        })
-                        .row_between(Pixels(2.0));
+/// The filters in front of one of the extra gain stages.
+fn stage_filter_controls(
+    cx: &mut Context,
+    label: &str,
+    stage: fn(&NonlinearAdaaParams) -> &StageParams,
+) {
+    VStack::new(cx, |cx| {
+        Label::new(cx, label).font_size(16.0);
+        knob_row(cx, |cx| {
+            knob(cx, move |params| &stage(params).highpass_cutoff);
+            knob(cx, move |params| &stage(params).lowpass_cutoff);
+        });
+    })
+    .height(Auto)
+    .row_between(Pixels(2.0));
+}
 
-                        VStack::new(cx, |cx| {
-                            Label::new(cx, "Oversampling");
-                            ParamSlider::new(cx, Data::params, |params| &params.os_level);
-                        })
-                        .row_between(Pixels(2.0));
+/// File name of the impulse response at `path`, for the cabinet label.
+fn ir_name(path: Option<&str>) -> String {
+    path.and_then(|path| Path::new(path).file_name())
+        .map_or("No impulse response loaded".to_string(), |name| {
+            name.to_string_lossy().into_owned()
+        })
+}
 
-                        VStack::new(cx, |cx| {
-                            Label::new(cx, "Mix");
-                            ParamSlider::new(cx, Data::params, |params| &params.dry_wet);
-                        })
-                        .row_between(Pixels(2.0));
-                    })
-                    .child_space(Percentage(5.0))
-                    //.border_width(Pixels(5.0))
-                    //.border_color(Color::black())
-                    .row_between(Pixels(10.0));
-                })
-                .child_left(Percentage(5.0))
-                .child_bottom(Percentage(10.0));
-                //.border_width(Pixels(5.0))
-                //.border_color(Color::black());
+/// Impulse response loading and the cabinet stage's parameters.
+fn cabinet_controls(cx: &mut Context) {
+    VStack::new(cx, |cx| {
+        Label::new(cx, "Cabinet").font_size(16.0);
+        button(cx, |params| &params.output.cab_enable);
 
-                VStack::new(cx, |cx| {
-                    VStack::new(cx, |cx| {
-                        Label::new(cx, "Input Level");
-                        PeakMeter::new(
-                            cx,
-                            Data::input_peak_meters.map(|peak_meter| {
-                                util::gain_to_db(peak_meter[0].load(Ordering::Relaxed))
-                            }),
-                            Some(Duration::from_millis(600)),
-                        );
-                        PeakMeter::new(
-                            cx,
-                            Data::input_peak_meters.map(|peak_meter| {
-                                util::gain_to_db(peak_meter[1].load(Ordering::Relaxed))
-                            }),
-                            Some(Duration::from_millis(600)),
-                        );
-                    })
-                    //.border_width(Pixels(5.0))
-                    //.border_color(Color::black())
-                    // .space(Percentage(10.0))
-                    .row_between(Pixels(5.0));
+        Label::new(cx, Data::ir_name);
 
-                    VStack::new(cx, |cx| {
-                        Label::new(cx, "Output Level");
-                        PeakMeter::new(
-                            cx,
-                            Data::output_peak_meters.map(|peak_meter| {
-                                util::gain_to_db(peak_meter[0].load(Ordering::Relaxed))
-                            }),
-                            Some(Duration::from_millis(600)),
-                        );
-                        PeakMeter::new(
-                            cx,
-                            Data::output_peak_meters.map(|peak_meter| {
-                                util::gain_to_db(peak_meter[1].load(Ordering::Relaxed))
-                            }),
-                            Some(Duration::from_millis(600)),
-                        );
-                    })
-                    //.border_width(Pixels(5.0))
-                    //.border_color(Color::black())
-                    .row_between(Pixels(5.0));
-                    // .space(Percentage(10.0));
-                })
-                .child_space(Percentage(1.0))
-                .child_bottom(Percentage(65.0))
-                //.border_width(Pixels(5.0))
-                //.border_color(Color::black())
-                .row_between(Pixels(10.0));
+        Textbox::new(cx, Data::ir_path_input)
+            .on_submit(|cx, path, _| {
+                cx.emit(CabinetEvent::SetPath(path));
+                cx.emit(CabinetEvent::Load);
             })
-            //.border_width(Pixels(5.0))
-            //.border_color(Color::black())
-            .col_between(Pixels(20.0));
+            .width(Stretch(1.0));
+
+        HStack::new(cx, |cx| {
+            Button::new(
+                cx,
+                |cx| cx.emit(CabinetEvent::Load),
+                |cx| Label::new(cx, "Load IR"),
+            );
+            Button::new(
+                cx,
+                |cx| cx.emit(CabinetEvent::Clear),
+                |cx| Label::new(cx, "Clear IR"),
+            );
         })
-        //.border_width(Pixels(5.0))
-        //.border_color(Color::black())
-        .row_between(Pixels(20.0));
+        .col_between(Pixels(5.0))
+        .height(Auto);
+
+        knob_row(cx, |cx| {
+            knob(cx, |params| &params.output.cab_trim);
+            knob(cx, |params| &params.output.cab_length);
+            knob(cx, |params| &params.output.cab_mix);
+        });
+    })
+    .row_between(Pixels(2.0));
+}
+
+/// Shape of the hysteresis style, shared by every slot and stage using it.
+fn hysteresis_controls(cx: &mut Context) {
+    VStack::new(cx, |cx| {
+        Label::new(cx, "Hysteresis").font_size(16.0);
+        knob_row(cx, |cx| {
+            knob(cx, |params| &params.nonlinearity.hysteresis_drive);
+            knob(cx, |params| &params.nonlinearity.hysteresis_saturation);
+            knob(cx, |params| &params.nonlinearity.hysteresis_width);
+        });
+    })
+    .row_between(Pixels(2.0));
+}
+
+/// Harmonic levels and signs of the Chebyshev style, with its transfer curve.
+fn chebyshev_controls(cx: &mut Context) {
+    VStack::new(cx, |cx| {
+        Label::new(cx, "Chebyshev Harmonics").font_size(16.0);
+        TransferCurve::new(cx, Data::params)
+            .width(Pixels(150.0))
+            .height(Pixels(150.0));
+
+        let harmonics: [fn(&NonlinearAdaaParams) -> &HarmonicParams; NUM_HARMONICS] = [
+            |params| &params.nonlinearity.harmonic_2,
+            |params| &params.nonlinearity.harmonic_3,
+            |params| &params.nonlinearity.harmonic_4,
+            |params| &params.nonlinearity.harmonic_5,
+            |params| &params.nonlinearity.harmonic_6,
+            |params| &params.nonlinearity.harmonic_7,
+            |params| &params.nonlinearity.harmonic_8,
+        ];
+        for harmonic in harmonics {
+            HStack::new(cx, |cx| {
+                knob(cx, move |params| &harmonic(params).level);
+                button(cx, move |params| &harmonic(params).invert)
+                    .top(Stretch(1.0))
+                    .bottom(Stretch(1.0));
+            })
+            .col_between(Pixels(4.0))
+            .height(Auto);
+        }
+    })
+    .row_between(Pixels(2.0));
+}
+
+/// Linked emphasis and de-emphasis shelves around the oversampled section.
+fn emphasis_controls(cx: &mut Context) {
+    VStack::new(cx, |cx| {
+        Label::new(cx, "Emphasis").font_size(16.0);
+        knob_row(cx, |cx| {
+            knob(cx, |params| &params.filter.emphasis_amount);
+            knob(cx, |params| &params.filter.emphasis_frequency);
+        });
+    })
+    .row_between(Pixels(2.0));
+}
+
+/// Filters in the feedback loop around the main nonlinearity. The amount sits with the
+/// nonlinearity.
+fn feedback_controls(cx: &mut Context) {
+    VStack::new(cx, |cx| {
+        Label::new(cx, "Feedback").font_size(16.0);
+        knob_row(cx, |cx| {
+            knob(cx, |params| &params.filter.feedback_highpass);
+            knob(cx, |params| &params.filter.feedback_lowpass);
+        });
+    })
+    .row_between(Pixels(2.0));
+}
+
+/// Input noise gate, applied before the prefilter.
+fn gate_controls(cx: &mut Context) {
+    VStack::new(cx, |cx| {
+        Label::new(cx, "Noise Gate").font_size(16.0);
+        button(cx, |params| &params.input.gate_enable);
+        knob_row(cx, |cx| {
+            knob(cx, |params| &params.input.gate_threshold);
+            knob(cx, |params| &params.input.gate_hysteresis);
+            knob(cx, |params| &params.input.gate_range);
+        });
+        knob_row(cx, |cx| {
+            knob(cx, |params| &params.input.gate_attack);
+            knob(cx, |params| &params.input.gate_hold);
+            knob(cx, |params| &params.input.gate_release);
+        });
+    })
+    .row_between(Pixels(2.0));
+}
+
+/// Output ceiling, applied after the dry/wet mix.
+fn ceiling_controls(cx: &mut Context) {
+    VStack::new(cx, |cx| {
+        Label::new(cx, "Output Ceiling").font_size(16.0);
+        slider(cx, |params| &params.output.ceiling_mode);
+        knob_row(cx, |cx| {
+            knob(cx, |params| &params.output.ceiling);
+            knob(cx, |params| &params.output.ceiling_release);
+        });
     })
+    .row_between(Pixels(2.0));
 }
@@ -0,0 +1,147 @@
+use nih_plug::prelude::util;
+use std::collections::VecDeque;
+use std::f32::consts::PI;
+
+use crate::filters::FirstOrderSection;
+
+// settings changes the de-emphasis can have queued, one per sample while the settings glide, so
+// this covers any oversampling latency
+const MAX_PENDING: usize = 1024;
+
+/// Linked pre-emphasis and de-emphasis high shelves. The de-emphasis shelf is the exact inverse
+/// of the pre-emphasis shelf (its poles are the other's zeros and vice versa), so the chain is
+/// flat while the signal stays out of the nonlinearity's curved region.
+///
+/// While the settings glide, the de-emphasis follows them delayed by the latency between the
+/// two, so it undoes each sample with the shelf that boosted it.
+pub struct Emphasis {
+    pre: [FirstOrderSection; 2],
+    post: [FirstOrderSection; 2],
+    amount_db: f32,
+    frequency: f32,
+    // settings the de-emphasis is using
+    post_amount_db: f32,
+    post_frequency: f32,
+    // settings the de-emphasis switches to, with the sample it switches on
+    pending: VecDeque<(u64, f32, f32)>,
+    // samples each side has processed
+    pre_clock: u64,
+    post_clock: u64,
+    sample_rate: f32,
+}
+
+impl Default for Emphasis {
+    fn default() -> Self {
+        Self {
+            pre: Default::default(),
+            post: Default::default(),
+            amount_db: 0.0,
+            frequency: 1000.0,
+            post_amount_db: 0.0,
+            post_frequency: 1000.0,
+            pending: VecDeque::with_capacity(MAX_PENDING),
+            pre_clock: 0,
+            post_clock: 0,
+            sample_rate: 44100.0,
+        }
+    }
+}
+
+/// Coefficients of `H(s) = (G s + w) / (s + w)` and its inverse through the bilinear transform,
+/// unity at DC and `G` at Nyquist.
+fn shelves(amount_db: f32, frequency: f32, sample_rate: f32) -> ([f32; 3], [f32; 3]) {
+    let g = util::db_to_gain(amount_db);
+    let k = (PI * frequency.clamp(1.0, sample_rate * 0.49) / sample_rate).tan();
+
+    let pre_norm = (1.0 + k).recip();
+    let post_norm = (g + k).recip();
+
+    (
+        [(g + k) * pre_norm, (k - g) * pre_norm, (k - 1.0) * pre_norm],
+        [
+            (1.0 + k) * post_norm,
+            (k - 1.0) * post_norm,
+            (k - g) * post_norm,
+        ],
+    )
+}
+
+impl Emphasis {
+    pub fn set_sample_rate(&mut self, sample_rate: f32) {
+        self.sample_rate = sample_rate;
+        self.update_pre();
+        self.update_post();
+    }
+
+    /// `amount_db` is the high frequency boost ahead of the nonlinearity and the cut after it.
+    /// Applies from the next sample into [`Emphasis::pre`]. The cut follows `latency` samples
+    /// later, when the samples boosted with these settings come out of [`Emphasis::post`].
+    pub fn set(&mut self, amount_db: f32, frequency: f32, latency: usize) {
+        if amount_db == self.amount_db && frequency == self.frequency {
+            return;
+        }
+
+        self.amount_db = amount_db;
+        self.frequency = frequency;
+        self.update_pre();
+
+        if self.pending.len() == MAX_PENDING {
+            // only reachable with a flood of tiny blocks, skip ahead rather than allocate
+            let (_, amount_db, frequency) = self.pending.pop_front().unwrap();
+            self.set_post(amount_db, frequency);
+        }
+        self.pending
+            .push_back((self.pre_clock + latency as u64, amount_db, frequency));
+    }
+
+    fn update_pre(&mut self) {
+        let ([b0, b1, a1], _) = shelves(self.amount_db, self.frequency, self.sample_rate);
+        self.pre
+            .iter_mut()
+            .for_each(|f| f.set_coefficients(b0, b1, a1));
+    }
+
+    fn update_post(&mut self) {
+        let (_, [b0, b1, a1]) = shelves(self.post_amount_db, self.post_frequency, self.sample_rate);
+        self.post
+            .iter_mut()
+            .for_each(|f| f.set_coefficients(b0, b1, a1));
+    }
+
+    fn set_post(&mut self, amount_db: f32, frequency: f32) {
+        self.post_amount_db = amount_db;
+        self.post_frequency = frequency;
+        self.update_post();
+    }
+
+    /// Boosts the highs of a stereo sample.
+    #[inline]
+    pub fn pre(&mut self, left: f32, right: f32) -> (f32, f32) {
+        self.pre_clock += 1;
+        (self.pre[0].process(left), self.pre[1].process(right))
+    }
+
+    /// Cuts the highs of a stereo sample, with the settings its input was boosted with.
+    #[inline]
+    pub fn post(&mut self, left: f32, right: f32) -> (f32, f32) {
+        while let Some(&(due, amount_db, frequency)) = self.pending.front() {
+            if due > self.post_clock {
+                break;
+            }
+            self.pending.pop_front();
+            self.set_post(amount_db, frequency);
+        }
+        self.post_clock += 1;
+
+        (self.post[0].process(left), self.post[1].process(right))
+    }
+
+    pub fn reset(&mut self) {
+        self.pre.iter_mut().for_each(|f| f.reset());
+        self.post.iter_mut().for_each(|f| f.reset());
+        self.pending.clear();
+        self.pre_clock = 0;
+        self.post_clock = 0;
+        self.set_post(self.amount_db, self.frequency);
+    }
+}
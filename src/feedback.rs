@@ -0,0 +1,107 @@
+use std::f32::consts::PI;
+
+use crate::filters::OnePole;
+
+const FEEDBACK_LOWPASS_DEFAULT: f32 = 20000.0;
+const FEEDBACK_HIGHPASS_DEFAULT: f32 = 10.0;
+// the fed back signal is softly limited to this level so the loop cannot run away
+const FEEDBACK_LIMIT: f32 = 4.0;
+
+/// One channel of the feedback loop around the main nonlinearity, running at the oversampled
+/// rate. The loop closes through one oversampled sample of delay. To compensate, the nonlinear
+/// output is extrapolated a sample ahead before it is filtered, which cancels the delay's phase
+/// lag at low frequencies. The extrapolation boosts the top of the spectrum, so it is scaled back
+/// wherever the feedback lowpass leaves too little attenuation to keep the loop gain below the
+/// feedback amount.
+pub struct FeedbackPath {
+    lowpass: OnePole,
+    highpass: OnePole,
+    lowpass_cutoff: f32,
+    highpass_cutoff: f32,
+    sample_rate: f32,
+    // how far the nonlinear output is extrapolated, `1.0` for a whole sample
+    prediction: f32,
+    // unfiltered nonlinear output from the previous sample
+    previous: f32,
+    // filtered nonlinear output from the previous sample
+    state: f32,
+}
+
+impl Default for FeedbackPath {
+    fn default() -> Self {
+        Self {
+            lowpass: OnePole::default(),
+            highpass: OnePole::default(),
+            lowpass_cutoff: FEEDBACK_LOWPASS_DEFAULT,
+            highpass_cutoff: FEEDBACK_HIGHPASS_DEFAULT,
+            sample_rate: 44100.0,
+            prediction: prediction(FEEDBACK_LOWPASS_DEFAULT, 44100.0),
+            previous: 0.0,
+            state: 0.0,
+        }
+    }
+}
+
+/// The largest extrapolation, up to a whole sample, whose boost the feedback lowpass still
+/// cancels at every frequency. Extrapolating by `c` samples is `(1 + c) - c z^-1`. With the
+/// lowpass' prewarped cutoff `wc`, their combined gain stays at or below unity as long as
+/// `(1 + 2c)^2 <= 1 + 1 / wc^2`.
+fn prediction(lowpass_cutoff: f32, sample_rate: f32) -> f32 {
+    let wc = (PI * lowpass_cutoff.clamp(1.0, sample_rate * 0.49) / sample_rate).tan();
+    (((1.0 + 1.0 / (wc * wc)).sqrt() - 1.0) / 2.0).min(1.0)
+}
+
+impl FeedbackPath {
+    pub fn set_sample_rate(&mut self, sample_rate: f32) {
+        self.sample_rate = sample_rate;
+        self.lowpass.set_cutoff(self.lowpass_cutoff, sample_rate);
+        self.highpass.set_cutoff(self.highpass_cutoff, sample_rate);
+        self.prediction = prediction(self.lowpass_cutoff, sample_rate);
+    }
+
+    pub fn set_cutoffs(&mut self, lowpass_cutoff: f32, highpass_cutoff: f32) {
+        if lowpass_cutoff != self.lowpass_cutoff {
+            self.lowpass_cutoff = lowpass_cutoff;
+            self.lowpass.set_cutoff(lowpass_cutoff, self.sample_rate);
+            self.prediction = prediction(lowpass_cutoff, self.sample_rate);
+        }
+        if highpass_cutoff != self.highpass_cutoff {
+            self.highpass_cutoff = highpass_cutoff;
+            self.highpass.set_cutoff(highpass_cutoff, self.sample_rate);
+        }
+    }
+
+    /// Adds the fed back signal to the nonlinearity's input.
+    #[inline]
+    pub fn input(&self, x: f32, amount: f32) -> f32 {
+        x + amount * self.state
+    }
+
+    /// Takes the nonlinearity's output for the next sample.
+    #[inline]
+    pub fn store(&mut self, y: f32) {
+        // where the output is headed by the time the next sample reads it
+        let predicted = y + self.prediction * (y - self.previous);
+        self.previous = y;
+
+        let filtered = self
+            .lowpass
+            .process_lowpass(self.highpass.process_highpass(predicted));
+
+        self.state = if filtered.is_finite() {
+            FEEDBACK_LIMIT * (filtered / FEEDBACK_LIMIT).tanh()
+        } else {
+            self.lowpass.reset();
+            self.highpass.reset();
+            self.previous = 0.0;
+            0.0
+        };
+    }
+
+    pub fn reset(&mut self) {
+        self.lowpass.reset();
+        self.highpass.reset();
+        self.previous = 0.0;
+        self.state = 0.0;
+    }
+}
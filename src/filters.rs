@@ -0,0 +1,82 @@
+use std::f32::consts::PI;
+
+/// Topology-preserving one pole filter giving lowpass and highpass outputs from the same state.
+/// Cheap enough to recalculate every sample while a cutoff is smoothing.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct OnePole {
+    g: f32,
+    s: f32,
+}
+
+impl OnePole {
+    pub fn new(cutoff: f32, sample_rate: f32) -> Self {
+        let mut filter = OnePole::default();
+        filter.set_cutoff(cutoff, sample_rate);
+        filter
+    }
+
+    pub fn set_cutoff(&mut self, cutoff: f32, sample_rate: f32) {
+        let wc = (PI * cutoff.clamp(1.0, sample_rate * 0.49) / sample_rate).tan();
+        self.g = wc / (1.0 + wc);
+    }
+
+    #[inline]
+    pub fn process_lowpass(&mut self, x: f32) -> f32 {
+        let v = (x - self.s) * self.g;
+        let lp = v + self.s;
+        self.s = lp + v;
+        lp
+    }
+
+    #[inline]
+    pub fn process_highpass(&mut self, x: f32) -> f32 {
+        x - self.process_lowpass(x)
+    }
+
+    pub fn reset(&mut self) {
+        self.s = 0.0;
+    }
+}
+
+/// First order IIR section, `y[n] = b0 x[n] + b1 x[n - 1] - a1 y[n - 1]`.
+#[derive(Clone, Copy, Debug)]
+pub struct FirstOrderSection {
+    b0: f32,
+    b1: f32,
+    a1: f32,
+    x1: f32,
+    y1: f32,
+}
+
+impl Default for FirstOrderSection {
+    fn default() -> Self {
+        Self {
+            b0: 1.0,
+            b1: 0.0,
+            a1: 0.0,
+            x1: 0.0,
+            y1: 0.0,
+        }
+    }
+}
+
+impl FirstOrderSection {
+    pub fn set_coefficients(&mut self, b0: f32, b1: f32, a1: f32) {
+        self.b0 = b0;
+        self.b1 = b1;
+        self.a1 = a1;
+    }
+
+    #[inline]
+    pub fn process(&mut self, x: f32) -> f32 {
+        let y = self.b0 * x + self.b1 * self.x1 - self.a1 * self.y1;
+        self.x1 = x;
+        self.y1 = y;
+        y
+    }
+
+    pub fn reset(&mut self) {
+        self.x1 = 0.0;
+        self.y1 = 0.0;
+    }
+}
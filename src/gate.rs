@@ -0,0 +1,117 @@
+use nih_plug::prelude::util;
+
+// release time of the peak detector feeding the gate's open/close decision
+const DETECTOR_RELEASE_MS: f32 = 10.0;
+
+/// Gate settings converted to the units used per sample.
+#[derive(Clone, Copy, Debug)]
+pub struct GateSettings {
+    pub open_threshold: f32,
+    pub close_threshold: f32,
+    pub attack_coeff: f32,
+    pub release_coeff: f32,
+    pub hold_samples: usize,
+    pub floor_gain: f32,
+}
+
+impl GateSettings {
+    /// `hysteresis_db` is how far below `threshold_db` the level has to fall before the gate
+    /// closes again, `range_db` is the attenuation applied while closed.
+    pub fn new(
+        sample_rate: f32,
+        threshold_db: f32,
+        hysteresis_db: f32,
+        attack_ms: f32,
+        hold_ms: f32,
+        release_ms: f32,
+        range_db: f32,
+    ) -> Self {
+        Self {
+            open_threshold: util::db_to_gain(threshold_db),
+            close_threshold: util::db_to_gain(threshold_db - hysteresis_db),
+            attack_coeff: time_to_coeff(attack_ms, sample_rate),
+            release_coeff: time_to_coeff(release_ms, sample_rate),
+            hold_samples: (hold_ms / 1000.0 * sample_rate) as usize,
+            floor_gain: util::db_to_gain(-range_db),
+        }
+    }
+}
+
+/// One pole coefficient reaching ~63% of a step in `time_ms`.
+fn time_to_coeff(time_ms: f32, sample_rate: f32) -> f32 {
+    if time_ms <= 0.0 {
+        0.0
+    } else {
+        (-1.0 / (time_ms / 1000.0 * sample_rate)).exp()
+    }
+}
+
+/// Stereo linked noise gate. The detector follows the louder of the two channels and the
+/// returned gain is applied to both.
+pub struct NoiseGate {
+    envelope: f32,
+    gain: f32,
+    hold_counter: usize,
+    open: bool,
+    detector_release_coeff: f32,
+}
+
+impl Default for NoiseGate {
+    fn default() -> Self {
+        Self {
+            envelope: 0.0,
+            gain: 1.0,
+            hold_counter: 0,
+            open: true,
+            detector_release_coeff: time_to_coeff(DETECTOR_RELEASE_MS, 44100.0),
+        }
+    }
+}
+
+impl NoiseGate {
+    pub fn set_sample_rate(&mut self, sample_rate: f32) {
+        self.detector_release_coeff = time_to_coeff(DETECTOR_RELEASE_MS, sample_rate);
+    }
+
+    /// Feeds the detector with the current input level and returns the gain to apply.
+    #[inline]
+    pub fn process(&mut self, level: f32, settings: &GateSettings) -> f32 {
+        self.envelope = if level > self.envelope {
+            level
+        } else {
+            level + self.detector_release_coeff * (self.envelope - level)
+        };
+
+        if self.envelope >= settings.open_threshold {
+            self.open = true;
+            self.hold_counter = settings.hold_samples;
+        } else if self.envelope < settings.close_threshold {
+            if self.hold_counter > 0 {
+                self.hold_counter -= 1;
+            } else {
+                self.open = false;
+            }
+        }
+
+        let (target, coeff) = if self.open {
+            (1.0, settings.attack_coeff)
+        } else {
+            (settings.floor_gain, settings.release_coeff)
+        };
+        self.gain = target + coeff * (self.gain - target);
+
+        self.gain
+    }
+
+    /// Current gain, `1.0` when fully open.
+    pub fn gain(&self) -> f32 {
+        self.gain
+    }
+
+    pub fn reset(&mut self) {
+        self.envelope = 0.0;
+        self.gain = 1.0;
+        self.hold_counter = 0;
+        self.open = true;
+    }
+}
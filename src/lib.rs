@@ -1,63 +1,261 @@
-use jdsp::{
-    AntiderivativeOrder, CircularDelayBuffer, NonlinearProcessor, ProcessorState,
-    ProcessorState::State, ProcessorStyle, MAX_LATENCY_AMT,
-};
+pub use analysis::AnalysisMode;
+use analysis::{AnalysisModeState, TestTone};
+use cabinet::{Cabinet, CabinetExchange, CABINET_LATENCY};
+use ceiling::{CeilingMode, OutputCeiling};
+use chebyshev::{ChebyshevShape, HarmonicParams, NUM_HARMONICS};
+use crossfade::CrossfadingProcessor;
+pub use diode::DiodePreset;
+use emphasis::Emphasis;
+use feedback::FeedbackPath;
+use gate::{GateSettings, NoiseGate};
+use hysteresis::HysteresisSettings;
+use jdsp::{AntiderivativeOrder, CircularDelayBuffer, MAX_LATENCY_AMT};
 use jdsp::{FilterOrder, IIRBiquadFilter};
 use jdsp::{Oversample, OversampleFactor};
+use midi::MidiCcMap;
 use nih_plug::prelude::*;
+use nih_plug::wrapper::state::PluginState;
 use nih_plug_vizia::ViziaState;
-use std::sync::atomic::Ordering;
-use std::sync::Arc;
-
+use randomize::{RandomizeTarget, Rng};
+use scope::Scope;
+use stages::{SaturationStage, StageParams, MAX_EXTRA_STAGES};
+use std::collections::BTreeSet;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, AtomicU32, Ordering};
+use std::sync::{Arc, RwLock};
+pub use styles::ClipStyle;
+use styles::StyleSettings;
+use theme::EditorTheme;
+
+mod analysis;
+mod cabinet;
+mod ceiling;
+mod chebyshev;
+mod crossfade;
+mod custom_widgets;
+mod diode;
 mod editor;
+mod emphasis;
+mod feedback;
+mod filters;
+mod gate;
+mod hysteresis;
+#[cfg(feature = "measure")]
+pub mod measure;
+mod midi;
+pub mod randomize;
+mod scope;
+mod stages;
+mod state;
+mod styles;
+#[cfg(feature = "measure")]
+pub mod test_vectors;
+mod theme;
+pub mod undo;
 
 const MAX_BLOCK_SIZE: usize = 32;
 const MAX_OS_FACTOR_SCALE: usize = 16;
 const PEAK_METER_DECAY_MS: f64 = 15.0;
 const PEAK_DECAY_FACTOR: f64 = 0.05;
 
+/// Work that is run off the audio thread.
+pub enum Task {
+    LoadImpulseResponse(PathBuf),
+    ClearImpulseResponse,
+}
+
 pub struct NonlinearAdaa {
     params: Arc<NonlinearAdaaParams>,
-    non_linear_processors: [NonlinearProcessor; 2],
-    proc_state: ProcessorState,
+    non_linear_processors: [CrossfadingProcessor; 2],
+    // second style slot, blended with the first by the morph parameter
+    morph_processors: [CrossfadingProcessor; 2],
     oversamplers: [Oversample; 2],
     over_sample_process_buf: [[f32; MAX_BLOCK_SIZE * MAX_OS_FACTOR_SCALE]; 2],
     pre_filters: [IIRBiquadFilter; 2],
     peak_meter_decay_weight: f32,
     input_meters: [Arc<AtomicF32>; 2],
     output_meters: [Arc<AtomicF32>; 2],
+    gate_meter: Arc<AtomicF32>,
+    scope: Arc<Scope>,
     mix_scratch_buffer: [[f32; MAX_BLOCK_SIZE]; 2],
     dry_delay: [CircularDelayBuffer; 2],
+    extra_stages: [[SaturationStage; 2]; MAX_EXTRA_STAGES],
+    feedback: [FeedbackPath; 2],
+    emphasis: Emphasis,
+    noise_gate: NoiseGate,
+    sample_rate: f32,
+    latency: usize,
+    dry_latency: usize,
+    cabinet: Box<Cabinet>,
+    cabinet_exchange: Arc<CabinetExchange>,
+    // read by the background thread when resampling impulse responses
+    background_sample_rate: Arc<AtomicF32>,
+    ceiling: OutputCeiling,
+    test_tone: TestTone,
+    // the randomize trigger's value in the previous block
+    randomize_trigger: bool,
+    // picks the values when the trigger fires with the editor closed
+    randomize_rng: Rng,
 }
 
 #[derive(Params, Debug)]
 pub struct NonlinearAdaaParams {
+    #[nested(group = "Input")]
+    pub input: InputParams,
+    #[nested(group = "Nonlinearity")]
+    pub nonlinearity: NonlinearityParams,
+    #[nested(group = "Filter")]
+    pub filter: FilterParams,
+    #[nested(group = "Output")]
+    pub output: OutputParams,
+    #[id = "plugin bypass"]
+    pub bypass: BoolParam,
+    /// Randomizes the main controls when it turns on.
+    #[id = "randomize"]
+    pub randomize: BoolParam,
+    /// Randomizer targets kept as they are.
+    #[persist = "randomize-locks"]
+    pub randomize_locks: RwLock<BTreeSet<RandomizeTarget>>,
+    /// Set by the audio thread when the randomize trigger turns on while the editor is open,
+    /// cleared by the editor once it has randomized.
+    pub randomize_requested: AtomicBool,
+    /// Set from the editor, only injects its tone while the editor is open.
+    pub analysis_mode: AnalysisModeState,
+    /// Impulse response file loaded into the cabinet stage.
+    #[persist = "ir-path"]
+    pub ir_path: RwLock<Option<String>>,
+    #[persist = "editor-state"]
+    editor_state: Arc<ViziaState>,
+    #[persist = "editor-theme"]
+    editor_theme: RwLock<EditorTheme>,
+    /// CC assignments made with MIDI learn.
+    #[persist = "midi-cc-map"]
+    pub midi_map: MidiCcMap,
+    /// Layout of the saved state, see [`state::migrate`].
+    #[persist = "state-version"]
+    state_version: AtomicU32,
+}
+
+/// Input gain, the noise gate ahead of it and the analysis tone that can replace the input.
+#[derive(Params, Debug)]
+pub struct InputParams {
     #[id = "gain"]
     pub gain: FloatParam,
-    #[id = "output"]
-    pub output: FloatParam,
+    #[id = "gate enable"]
+    pub gate_enable: BoolParam,
+    #[id = "gate threshold"]
+    pub gate_threshold: FloatParam,
+    #[id = "gate hysteresis"]
+    pub gate_hysteresis: FloatParam,
+    #[id = "gate attack"]
+    pub gate_attack: FloatParam,
+    #[id = "gate hold"]
+    pub gate_hold: FloatParam,
+    #[id = "gate release"]
+    pub gate_release: FloatParam,
+    #[id = "gate range"]
+    pub gate_range: FloatParam,
+    #[id = "analysis frequency"]
+    pub analysis_frequency: FloatParam,
+    #[id = "analysis level"]
+    pub analysis_level: FloatParam,
+}
+
+/// The clipping styles and their settings, oversampling, the feedback amount and the extra gain
+/// stages.
+#[derive(Params, Debug)]
+pub struct NonlinearityParams {
     #[id = "nl proc"]
-    pub nl_proc_type: EnumParam<ProcessorStyle>,
+    pub nl_proc_type: EnumParam<ClipStyle>,
+    #[id = "nl proc b"]
+    pub nl_proc_type_b: EnumParam<ClipStyle>,
     #[id = "ad level"]
     pub nl_proc_order: EnumParam<AntiderivativeOrder>,
     #[id = "os level"]
     pub os_level: EnumParam<OversampleFactor>,
+    #[id = "morph"]
+    pub morph: FloatParam,
+    #[id = "feedback"]
+    pub feedback: FloatParam,
+    #[id = "hyst drive"]
+    pub hysteresis_drive: FloatParam,
+    #[id = "hyst saturation"]
+    pub hysteresis_saturation: FloatParam,
+    #[id = "hyst width"]
+    pub hysteresis_width: FloatParam,
+    #[id = "diode preset"]
+    pub diode_preset: EnumParam<DiodePreset>,
+    #[nested(id_prefix = "harmonic 2", group = "Harmonic 2")]
+    pub harmonic_2: HarmonicParams,
+    #[nested(id_prefix = "harmonic 3", group = "Harmonic 3")]
+    pub harmonic_3: HarmonicParams,
+    #[nested(id_prefix = "harmonic 4", group = "Harmonic 4")]
+    pub harmonic_4: HarmonicParams,
+    #[nested(id_prefix = "harmonic 5", group = "Harmonic 5")]
+    pub harmonic_5: HarmonicParams,
+    #[nested(id_prefix = "harmonic 6", group = "Harmonic 6")]
+    pub harmonic_6: HarmonicParams,
+    #[nested(id_prefix = "harmonic 7", group = "Harmonic 7")]
+    pub harmonic_7: HarmonicParams,
+    #[nested(id_prefix = "harmonic 8", group = "Harmonic 8")]
+    pub harmonic_8: HarmonicParams,
+    #[id = "stage count"]
+    pub num_stages: IntParam,
+    #[nested(id_prefix = "stage 2", group = "Stage 2")]
+    pub stage_2: StageParams,
+    #[nested(id_prefix = "stage 3", group = "Stage 3")]
+    pub stage_3: StageParams,
+    #[nested(id_prefix = "stage 4", group = "Stage 4")]
+    pub stage_4: StageParams,
+}
+
+/// The prefilter, emphasis and feedback filters.
+#[derive(Params, Debug)]
+pub struct FilterParams {
     #[id = "pre filter cutoff"]
     pub pre_filter_cutoff: FloatParam,
+    #[id = "emphasis"]
+    pub emphasis_amount: FloatParam,
+    #[id = "emphasis frequency"]
+    pub emphasis_frequency: FloatParam,
+    #[id = "feedback lowpass"]
+    pub feedback_lowpass: FloatParam,
+    #[id = "feedback highpass"]
+    pub feedback_highpass: FloatParam,
+}
+
+/// Output gain, the dry/wet mix, the cabinet stage and the output ceiling.
+#[derive(Params, Debug)]
+pub struct OutputParams {
+    #[id = "output"]
+    pub gain: FloatParam,
     #[id = "dry wet"]
     pub dry_wet: FloatParam,
-    #[id = "plugin bypass"]
-    pub bypass: BoolParam,
-    #[persist = "editor-state"]
-    editor_state: Arc<ViziaState>,
+    #[id = "cab enable"]
+    pub cab_enable: BoolParam,
+    #[id = "cab trim"]
+    pub cab_trim: FloatParam,
+    #[id = "cab length"]
+    pub cab_length: FloatParam,
+    #[id = "cab mix"]
+    pub cab_mix: FloatParam,
+    #[id = "ceiling mode"]
+    pub ceiling_mode: EnumParam<CeilingMode>,
+    #[id = "ceiling"]
+    pub ceiling: FloatParam,
+    #[id = "ceiling release"]
+    pub ceiling_release: FloatParam,
 }
 
 impl Default for NonlinearAdaa {
     fn default() -> Self {
+        let params = Arc::new(NonlinearAdaaParams::new());
+        params.midi_map.set_params(params.param_map());
+
         Self {
-            params: Arc::new(NonlinearAdaaParams::new()),
-            non_linear_processors: [NonlinearProcessor::new(), NonlinearProcessor::new()],
-            proc_state: State(ProcessorStyle::HardClip, AntiderivativeOrder::FirstOrder),
+            params,
+            non_linear_processors: Default::default(),
+            morph_processors: Default::default(),
             oversamplers: [
                 Oversample::new(OversampleFactor::TwoTimes, MAX_BLOCK_SIZE),
                 Oversample::new(OversampleFactor::TwoTimes, MAX_BLOCK_SIZE),
@@ -73,11 +271,27 @@ impl Default for NonlinearAdaa {
                 Arc::new(AtomicF32::new(util::MINUS_INFINITY_DB)),
                 Arc::new(AtomicF32::new(util::MINUS_INFINITY_DB)),
             ],
+            gate_meter: Arc::new(AtomicF32::new(1.0)),
+            scope: Arc::new(Scope::default()),
             mix_scratch_buffer: [[0.0_f32; MAX_BLOCK_SIZE]; 2],
             dry_delay: [
                 CircularDelayBuffer::new(MAX_LATENCY_AMT),
                 CircularDelayBuffer::new(MAX_LATENCY_AMT),
             ],
+            extra_stages: Default::default(),
+            feedback: Default::default(),
+            emphasis: Emphasis::default(),
+            noise_gate: NoiseGate::default(),
+            sample_rate: 44100.0,
+            latency: 0,
+            dry_latency: 0,
+            cabinet: Box::new(Cabinet::empty(44100.0)),
+            cabinet_exchange: Arc::new(CabinetExchange::default()),
+            background_sample_rate: Arc::new(AtomicF32::new(44100.0)),
+            ceiling: OutputCeiling::default(),
+            test_tone: TestTone::default(),
+            randomize_trigger: false,
+            randomize_rng: Rng::from_time(),
         }
     }
 }
@@ -87,81 +301,517 @@ impl NonlinearAdaaParams {
         let oversampling_times = Arc::new(AtomicF32::new(oversampling_factor_to_times(
             OversampleFactor::TwoTimes,
         )));
+        let os_level_times = oversampling_times.clone();
 
         Self {
             editor_state: editor::default_state(),
+            editor_theme: RwLock::new(EditorTheme::default()),
+            midi_map: MidiCcMap::default(),
+            state_version: AtomicU32::new(state::STATE_VERSION),
+
+            input: InputParams {
+                gain: FloatParam::new(
+                    "Gain",
+                    util::db_to_gain(0.0),
+                    FloatRange::Skewed {
+                        min: util::db_to_gain(0.0),
+                        max: util::db_to_gain(60.0),
+                        factor: FloatRange::gain_skew_factor(0.0, 60.0),
+                    },
+                )
+                .with_smoother(SmoothingStyle::OversamplingAware(
+                    oversampling_times.clone(),
+                    &SmoothingStyle::Logarithmic(1000.0),
+                ))
+                .with_unit(" dB")
+                .with_value_to_string(formatters::v2s_f32_gain_to_db(2))
+                .with_string_to_value(formatters::s2v_f32_gain_to_db()),
+
+                gate_enable: BoolParam::new("Gate Enable", false),
+
+                gate_threshold: FloatParam::new(
+                    "Gate Threshold",
+                    -60.0,
+                    FloatRange::Linear {
+                        min: -100.0,
+                        max: 0.0,
+                    },
+                )
+                .with_unit(" dB")
+                .with_value_to_string(formatters::v2s_f32_rounded(1)),
+
+                gate_hysteresis: FloatParam::new(
+                    "Gate Hysteresis",
+                    6.0,
+                    FloatRange::Linear {
+                        min: 0.0,
+                        max: 24.0,
+                    },
+                )
+                .with_unit(" dB")
+                .with_value_to_string(formatters::v2s_f32_rounded(1)),
+
+                gate_attack: FloatParam::new(
+                    "Gate Attack",
+                    1.0,
+                    FloatRange::Skewed {
+                        min: 0.05,
+                        max: 50.0,
+                        factor: FloatRange::skew_factor(-1.0),
+                    },
+                )
+                .with_unit(" ms")
+                .with_value_to_string(formatters::v2s_f32_rounded(2)),
+
+                gate_hold: FloatParam::new(
+                    "Gate Hold",
+                    50.0,
+                    FloatRange::Linear {
+                        min: 0.0,
+                        max: 500.0,
+                    },
+                )
+                .with_unit(" ms")
+                .with_value_to_string(formatters::v2s_f32_rounded(0)),
+
+                gate_release: FloatParam::new(
+                    "Gate Release",
+                    100.0,
+                    FloatRange::Skewed {
+                        min: 5.0,
+                        max: 2000.0,
+                        factor: FloatRange::skew_factor(-1.0),
+                    },
+                )
+                .with_unit(" ms")
+                .with_value_to_string(formatters::v2s_f32_rounded(0)),
+
+                gate_range: FloatParam::new(
+                    "Gate Range",
+                    80.0,
+                    FloatRange::Linear {
+                        min: 0.0,
+                        max: 100.0,
+                    },
+                )
+                .with_unit(" dB")
+                .with_value_to_string(formatters::v2s_f32_rounded(1)),
+
+                analysis_frequency: FloatParam::new(
+                    "Analysis Frequency",
+                    1000.0,
+                    FloatRange::Skewed {
+                        min: 50.0,
+                        max: 5000.0,
+                        factor: FloatRange::skew_factor(-1.0),
+                    },
+                )
+                .non_automatable()
+                .with_value_to_string(formatters::v2s_f32_hz_then_khz(0))
+                .with_string_to_value(formatters::s2v_f32_hz_then_khz()),
+
+                analysis_level: FloatParam::new(
+                    "Analysis Level",
+                    -12.0,
+                    FloatRange::Linear {
+                        min: -40.0,
+                        max: 0.0,
+                    },
+                )
+                .non_automatable()
+                .with_unit(" dB")
+                .with_value_to_string(formatters::v2s_f32_rounded(1)),
+            },
+
+            nonlinearity: NonlinearityParams {
+                nl_proc_type: EnumParam::new("Nonlinear Process", ClipStyle::HardClip),
+
+                nl_proc_type_b: EnumParam::new("Nonlinear Process B", ClipStyle::Tanh),
+
+                nl_proc_order: EnumParam::new(
+                    "Antiderivative Order",
+                    AntiderivativeOrder::FirstOrder,
+                ),
+
+                os_level: EnumParam::new("Oversample Factor", OversampleFactor::TwoTimes)
+                    .with_callback(Arc::new(move |new_factor| {
+                        os_level_times.store(
+                            oversampling_factor_to_times(new_factor) as f32,
+                            Ordering::Relaxed,
+                        );
+                    })),
+
+                morph: FloatParam::new("Morph", 0.0, FloatRange::Linear { min: 0.0, max: 1.0 })
+                    .with_smoother(SmoothingStyle::OversamplingAware(
+                        oversampling_times.clone(),
+                        &SmoothingStyle::Linear(50.0),
+                    ))
+                    .with_value_to_string(formatters::v2s_f32_percentage(1))
+                    .with_string_to_value(formatters::s2v_f32_percentage()),
+
+                feedback: FloatParam::new(
+                    "Feedback",
+                    0.0,
+                    FloatRange::Linear {
+                        min: -0.95,
+                        max: 0.95,
+                    },
+                )
+                .with_smoother(SmoothingStyle::OversamplingAware(
+                    oversampling_times.clone(),
+                    &SmoothingStyle::Linear(50.0),
+                ))
+                .with_value_to_string(formatters::v2s_f32_percentage(1))
+                .with_string_to_value(formatters::s2v_f32_percentage()),
+
+                hysteresis_drive: FloatParam::new(
+                    "Hysteresis Drive",
+                    0.5,
+                    FloatRange::Linear { min: 0.0, max: 1.0 },
+                )
+                .with_value_to_string(formatters::v2s_f32_percentage(1))
+                .with_string_to_value(formatters::s2v_f32_percentage()),
+
+                hysteresis_saturation: FloatParam::new(
+                    "Hysteresis Saturation",
+                    0.5,
+                    FloatRange::Linear { min: 0.0, max: 1.0 },
+                )
+                .with_value_to_string(formatters::v2s_f32_percentage(1))
+                .with_string_to_value(formatters::s2v_f32_percentage()),
+
+                hysteresis_width: FloatParam::new(
+                    "Hysteresis Width",
+                    0.5,
+                    FloatRange::Linear { min: 0.0, max: 1.0 },
+                )
+                .with_value_to_string(formatters::v2s_f32_percentage(1))
+                .with_string_to_value(formatters::s2v_f32_percentage()),
+
+                diode_preset: EnumParam::new("Diode Clipper Preset", DiodePreset::Silicon),
 
-            gain: FloatParam::new(
-                "Gain",
-                util::db_to_gain(0.0),
-                FloatRange::Skewed {
-                    min: util::db_to_gain(0.0),
-                    max: util::db_to_gain(60.0),
-                    factor: FloatRange::gain_skew_factor(0.0, 60.0),
-                },
-            )
-            .with_smoother(SmoothingStyle::OversamplingAware(
-                oversampling_times.clone(),
-                &SmoothingStyle::Logarithmic(1000.0),
-            ))
-            .with_unit(" dB")
-            .with_value_to_string(formatters::v2s_f32_gain_to_db(2))
-            .with_string_to_value(formatters::s2v_f32_gain_to_db()),
-
-            output: FloatParam::new(
-                "Output Gain",
-                util::db_to_gain(-1.0),
-                FloatRange::Skewed {
-                    min: util::db_to_gain(-60.0),
-                    max: util::db_to_gain(0.0),
-                    factor: FloatRange::gain_skew_factor(-60.0, 0.0),
-                },
-            )
-            .with_unit(" dB")
-            // The value does not go down to 0 so we can do logarithmic here
-            .with_smoother(SmoothingStyle::Logarithmic(5.0))
-            .with_value_to_string(formatters::v2s_f32_gain_to_db(2))
-            .with_string_to_value(formatters::s2v_f32_gain_to_db())
-            .with_smoother(SmoothingStyle::OversamplingAware(
-                oversampling_times.clone(),
-                &SmoothingStyle::Logarithmic(5.0),
-            )),
-
-            nl_proc_type: EnumParam::new("Nonlinear Process", ProcessorStyle::HardClip),
-
-            nl_proc_order: EnumParam::new("Antiderivative Order", AntiderivativeOrder::FirstOrder),
-
-            dry_wet: FloatParam::new("Mix Amount", 1.0, FloatRange::Linear { min: 0.0, max: 1.0 })
+                harmonic_2: HarmonicParams::new(2),
+
+                harmonic_3: HarmonicParams::new(3),
+
+                harmonic_4: HarmonicParams::new(4),
+
+                harmonic_5: HarmonicParams::new(5),
+
+                harmonic_6: HarmonicParams::new(6),
+
+                harmonic_7: HarmonicParams::new(7),
+
+                harmonic_8: HarmonicParams::new(8),
+
+                num_stages: IntParam::new(
+                    "Stage Count",
+                    1,
+                    IntRange::Linear {
+                        min: 1,
+                        max: MAX_EXTRA_STAGES as i32 + 1,
+                    },
+                ),
+
+                stage_2: StageParams::new(2, oversampling_times.clone()),
+
+                stage_3: StageParams::new(3, oversampling_times.clone()),
+
+                stage_4: StageParams::new(4, oversampling_times.clone()),
+            },
+
+            filter: FilterParams {
+                pre_filter_cutoff: FloatParam::new(
+                    "Prefilter Cutoff Frequency",
+                    20000.0,
+                    FloatRange::Skewed {
+                        min: 50.,
+                        max: 22050.0 * 0.99,
+                        factor: FloatRange::skew_factor(-1.0),
+                    },
+                )
+                .with_smoother(SmoothingStyle::Logarithmic(10.0))
+                .with_value_to_string(formatters::v2s_f32_hz_then_khz(0))
+                .with_string_to_value(formatters::s2v_f32_hz_then_khz()),
+
+                emphasis_amount: FloatParam::new(
+                    "Emphasis",
+                    0.0,
+                    FloatRange::Linear {
+                        min: 0.0,
+                        max: 24.0,
+                    },
+                )
+                .with_smoother(SmoothingStyle::Linear(50.0))
+                .with_unit(" dB")
+                .with_value_to_string(formatters::v2s_f32_rounded(1)),
+
+                emphasis_frequency: FloatParam::new(
+                    "Emphasis Frequency",
+                    2000.0,
+                    FloatRange::Skewed {
+                        min: 200.0,
+                        max: 10000.0,
+                        factor: FloatRange::skew_factor(-1.0),
+                    },
+                )
+                .with_smoother(SmoothingStyle::Logarithmic(50.0))
+                .with_value_to_string(formatters::v2s_f32_hz_then_khz(0))
+                .with_string_to_value(formatters::s2v_f32_hz_then_khz()),
+
+                feedback_lowpass: FloatParam::new(
+                    "Feedback Lowpass",
+                    20000.0,
+                    FloatRange::Skewed {
+                        min: 200.0,
+                        max: 20000.0,
+                        factor: FloatRange::skew_factor(-1.0),
+                    },
+                )
+                .with_smoother(SmoothingStyle::OversamplingAware(
+                    oversampling_times.clone(),
+                    &SmoothingStyle::Logarithmic(10.0),
+                ))
+                .with_value_to_string(formatters::v2s_f32_hz_then_khz(0))
+                .with_string_to_value(formatters::s2v_f32_hz_then_khz()),
+
+                feedback_highpass: FloatParam::new(
+                    "Feedback Highpass",
+                    10.0,
+                    FloatRange::Skewed {
+                        min: 10.0,
+                        max: 2000.0,
+                        factor: FloatRange::skew_factor(-1.0),
+                    },
+                )
+                .with_smoother(SmoothingStyle::OversamplingAware(
+                    oversampling_times.clone(),
+                    &SmoothingStyle::Logarithmic(10.0),
+                ))
+                .with_value_to_string(formatters::v2s_f32_hz_then_khz(0))
+                .with_string_to_value(formatters::s2v_f32_hz_then_khz()),
+            },
+
+            output: OutputParams {
+                gain: FloatParam::new(
+                    "Output Gain",
+                    util::db_to_gain(-1.0),
+                    FloatRange::Skewed {
+                        min: util::db_to_gain(-60.0),
+                        max: util::db_to_gain(0.0),
+                        factor: FloatRange::gain_skew_factor(-60.0, 0.0),
+                    },
+                )
+                .with_unit(" dB")
+                // The value does not go down to 0 so we can do logarithmic here
+                .with_smoother(SmoothingStyle::Logarithmic(5.0))
+                .with_value_to_string(formatters::v2s_f32_gain_to_db(2))
+                .with_string_to_value(formatters::s2v_f32_gain_to_db())
+                .with_smoother(SmoothingStyle::OversamplingAware(
+                    oversampling_times,
+                    &SmoothingStyle::Logarithmic(5.0),
+                )),
+
+                dry_wet: FloatParam::new(
+                    "Mix Amount",
+                    1.0,
+                    FloatRange::Linear { min: 0.0, max: 1.0 },
+                )
                 .with_smoother(SmoothingStyle::Linear(50.0))
                 .with_value_to_string(formatters::v2s_f32_percentage(1))
                 .with_string_to_value(formatters::s2v_f32_percentage()),
 
-            os_level: EnumParam::new("Oversample Factor", OversampleFactor::TwoTimes)
-                .with_callback(Arc::new(move |new_factor| {
-                    oversampling_times.store(
-                        oversampling_factor_to_times(new_factor) as f32,
-                        Ordering::Relaxed,
-                    );
-                })),
-
-            pre_filter_cutoff: FloatParam::new(
-                "Prefilter Cutoff Frequency",
-                20000.0,
-                FloatRange::Skewed {
-                    min: 50.,
-                    max: 22050.0 * 0.99,
-                    factor: FloatRange::skew_factor(-1.0),
-                },
-            )
-            .with_smoother(SmoothingStyle::Logarithmic(10.0))
-            .with_value_to_string(formatters::v2s_f32_hz_then_khz(0))
-            .with_string_to_value(formatters::s2v_f32_hz_then_khz()),
+                cab_enable: BoolParam::new("Cabinet Enable", false),
+
+                cab_trim: FloatParam::new(
+                    "Cabinet Trim",
+                    util::db_to_gain(0.0),
+                    FloatRange::Skewed {
+                        min: util::db_to_gain(-24.0),
+                        max: util::db_to_gain(24.0),
+                        factor: FloatRange::gain_skew_factor(-24.0, 24.0),
+                    },
+                )
+                .with_smoother(SmoothingStyle::Logarithmic(50.0))
+                .with_unit(" dB")
+                .with_value_to_string(formatters::v2s_f32_gain_to_db(2))
+                .with_string_to_value(formatters::s2v_f32_gain_to_db()),
+
+                cab_length: FloatParam::new(
+                    "Cabinet IR Length",
+                    cabinet::MAX_IR_SECONDS * 1000.0,
+                    FloatRange::Skewed {
+                        min: 10.0,
+                        max: cabinet::MAX_IR_SECONDS * 1000.0,
+                        factor: FloatRange::skew_factor(-1.0),
+                    },
+                )
+                .with_unit(" ms")
+                .with_value_to_string(formatters::v2s_f32_rounded(0)),
+
+                cab_mix: FloatParam::new(
+                    "Cabinet Mix",
+                    1.0,
+                    FloatRange::Linear { min: 0.0, max: 1.0 },
+                )
+                .with_smoother(SmoothingStyle::Linear(50.0))
+                .with_value_to_string(formatters::v2s_f32_percentage(1))
+                .with_string_to_value(formatters::s2v_f32_percentage()),
+
+                ceiling_mode: EnumParam::new("Output Ceiling Mode", CeilingMode::Off),
+
+                ceiling: FloatParam::new(
+                    "Output Ceiling",
+                    util::db_to_gain(-0.3),
+                    FloatRange::Skewed {
+                        min: util::db_to_gain(-24.0),
+                        max: util::db_to_gain(0.0),
+                        factor: FloatRange::gain_skew_factor(-24.0, 0.0),
+                    },
+                )
+                .with_unit(" dB")
+                .with_value_to_string(formatters::v2s_f32_gain_to_db(2))
+                .with_string_to_value(formatters::s2v_f32_gain_to_db()),
+
+                ceiling_release: FloatParam::new(
+                    "Ceiling Release",
+                    100.0,
+                    FloatRange::Skewed {
+                        min: 10.0,
+                        max: 1000.0,
+                        factor: FloatRange::skew_factor(-1.0),
+                    },
+                )
+                .with_unit(" ms")
+                .with_value_to_string(formatters::v2s_f32_rounded(0)),
+            },
 
             bypass: BoolParam::new("Plugin Bypass", false),
+
+            randomize: BoolParam::new("Randomize", false),
+            randomize_locks: RwLock::new(BTreeSet::new()),
+            randomize_requested: AtomicBool::new(false),
+            analysis_mode: AnalysisModeState::default(),
+
+            ir_path: RwLock::new(None),
         }
     }
 }
 
+impl NonlinearityParams {
+    /// The Chebyshev shaper's harmonics, 2nd to 8th.
+    fn harmonics(&self) -> [&HarmonicParams; NUM_HARMONICS] {
+        [
+            &self.harmonic_2,
+            &self.harmonic_3,
+            &self.harmonic_4,
+            &self.harmonic_5,
+            &self.harmonic_6,
+            &self.harmonic_7,
+            &self.harmonic_8,
+        ]
+    }
+
+    /// The Chebyshev shaper described by the current harmonic settings.
+    pub(crate) fn chebyshev_shape(&self, midi_map: &MidiCcMap) -> ChebyshevShape {
+        ChebyshevShape::new(&self.harmonics().map(|harmonic| harmonic.weight(midi_map)))
+    }
+
+    /// The stages following the main nonlinearity, in processing order.
+    fn extra_stages(&self) -> [&StageParams; MAX_EXTRA_STAGES] {
+        [&self.stage_2, &self.stage_3, &self.stage_4]
+    }
+}
+
+impl NonlinearAdaa {
+    /// Input and output meter levels as `[input left, input right, output left, output right]`.
+    /// They only move while the editor is open and read zero otherwise.
+    pub fn meter_levels(&self) -> [f32; 4] {
+        [
+            self.input_meters[0].load(Ordering::Relaxed),
+            self.input_meters[1].load(Ordering::Relaxed),
+            self.output_meters[0].load(Ordering::Relaxed),
+            self.output_meters[1].load(Ordering::Relaxed),
+        ]
+    }
+
+    /// Latency of the wet path ahead of the dry/wet mix: the oversampling filters plus the
+    /// cabinet stage, if enabled. The dry signal is delayed by this much.
+    fn wet_latency(&self) -> usize {
+        let cabinet_latency = if self.params.midi_map.value(&self.params.output.cab_enable) {
+            CABINET_LATENCY
+        } else {
+            0
+        };
+
+        self.oversamplers[0].get_latency_samples() + cabinet_latency
+    }
+
+    /// Latency reported to the host. The output ceiling runs after the mix so it delays the dry
+    /// and wet signals alike.
+    fn total_latency(&self) -> usize {
+        self.wet_latency()
+            + self
+                .ceiling
+                .latency(self.params.midi_map.value(&self.params.output.ceiling_mode))
+    }
+
+    /// Moves the parameter mapped to an incoming CC, or maps it if a MIDI learn is waiting.
+    fn handle_event(&self, event: PluginNoteEvent<Self>) {
+        if let NoteEvent::MidiCC { cc, value, .. } = event {
+            if let Some(param) = self.params.midi_map.handle_cc(cc, value) {
+                // SAFETY: the parameter lives in `self.params`
+                unsafe { midi::apply_cc(param, value, self.sample_rate) };
+            }
+        }
+    }
+
+    /// Retunes everything that runs inside the oversampled loop to the current oversampled rate.
+    fn update_oversampled_rate(&mut self) {
+        let os_sample_rate = self.sample_rate
+            * oversampling_factor_to_times(self.oversamplers[0].get_oversample_factor());
+
+        self.extra_stages
+            .iter_mut()
+            .flatten()
+            .for_each(|stage| stage.set_sample_rate(os_sample_rate));
+
+        self.feedback
+            .iter_mut()
+            .for_each(|path| path.set_sample_rate(os_sample_rate));
+
+        self.non_linear_processors
+            .iter_mut()
+            .chain(self.morph_processors.iter_mut())
+            .for_each(|nl| nl.set_sample_rate(os_sample_rate));
+    }
+
+    /// Clears the nonlinear processors' history and puts them straight into the current states,
+    /// without crossfading.
+    fn reset_processors(&mut self) {
+        let cc = &self.params.midi_map;
+        let order = cc.value(&self.params.nonlinearity.nl_proc_order);
+        let style = cc.value(&self.params.nonlinearity.nl_proc_type);
+        let style_b = cc.value(&self.params.nonlinearity.nl_proc_type_b);
+
+        self.non_linear_processors
+            .iter_mut()
+            .for_each(|nl| nl.reset(style, order));
+        self.morph_processors
+            .iter_mut()
+            .for_each(|nl| nl.reset(style_b, order));
+
+        self.extra_stages
+            .iter_mut()
+            .zip(self.params.nonlinearity.extra_stages())
+            .for_each(|(stage, stage_params)| {
+                stage.iter_mut().for_each(|s| {
+                    s.reset(cc.value(&stage_params.style), cc.value(&stage_params.order))
+                });
+            });
+    }
+}
+
 #[inline]
 fn oversampling_factor_to_times(factor: OversampleFactor) -> f32 {
     match factor {
@@ -195,7 +845,7 @@ impl Plugin for NonlinearAdaa {
         names: PortNames::const_default(),
     }];
 
-    const MIDI_INPUT: MidiConfig = MidiConfig::None;
+    const MIDI_INPUT: MidiConfig = MidiConfig::MidiCCs;
     const MIDI_OUTPUT: MidiConfig = MidiConfig::None;
 
     const SAMPLE_ACCURATE_AUTOMATION: bool = true;
@@ -207,18 +857,53 @@ impl Plugin for NonlinearAdaa {
     // More advanced plugins can use this to run expensive background tasks. See the field's
     // documentation for more information. `()` means that the plugin does not have any background
     // tasks.
-    type BackgroundTask = ();
+    type BackgroundTask = Task;
 
     fn params(&self) -> Arc<dyn Params> {
         self.params.clone()
     }
 
-    fn editor(&mut self, _async_executor: AsyncExecutor<Self>) -> Option<Box<dyn Editor>> {
+    fn filter_state(state: &mut PluginState) {
+        state::migrate(state);
+    }
+
+    fn task_executor(&mut self) -> TaskExecutor<Self> {
+        let params = self.params.clone();
+        let cabinet_exchange = self.cabinet_exchange.clone();
+        let sample_rate = self.background_sample_rate.clone();
+
+        Box::new(move |task| match task {
+            Task::LoadImpulseResponse(path) => {
+                match Cabinet::load(&path, sample_rate.load(Ordering::Relaxed)) {
+                    Ok(cabinet) => {
+                        *params.ir_path.write().unwrap() = cabinet.path().map(String::from);
+                        cabinet_exchange.submit(cabinet);
+                    }
+                    Err(err) => {
+                        nih_error!(
+                            "Could not load impulse response {}: {}",
+                            path.display(),
+                            err
+                        )
+                    }
+                }
+            }
+            Task::ClearImpulseResponse => {
+                *params.ir_path.write().unwrap() = None;
+                cabinet_exchange.submit(Cabinet::empty(sample_rate.load(Ordering::Relaxed)));
+            }
+        })
+    }
+
+    fn editor(&mut self, async_executor: AsyncExecutor<Self>) -> Option<Box<dyn Editor>> {
         editor::create(
             self.params.clone(),
             self.input_meters.clone(),
             self.output_meters.clone(),
+            self.gate_meter.clone(),
+            self.scope.clone(),
             self.params.editor_state.clone(),
+            async_executor,
         )
     }
 
@@ -233,29 +918,44 @@ impl Plugin for NonlinearAdaa {
             .expect("Plugin was initialized without any outputs")
             .get() as usize;
 
+        self.sample_rate = buffer_config.sample_rate;
+
         self.pre_filters.iter_mut().for_each(|filter| {
             filter.init(&buffer_config.sample_rate, &20000.0, FilterOrder::First);
         });
 
-        let new_state = State(
-            self.params.nl_proc_type.value(),
-            self.params.nl_proc_order.value(),
-        );
+        self.update_oversampled_rate();
 
-        self.proc_state = new_state;
+        self.noise_gate.set_sample_rate(buffer_config.sample_rate);
 
-        self.non_linear_processors
-            .iter_mut()
-            .for_each(|x| *x = NonlinearProcessor::new());
+        self.emphasis.set_sample_rate(buffer_config.sample_rate);
+
+        self.ceiling.initialize(buffer_config.sample_rate);
+
+        self.reset_processors();
 
+        self.latency = self.total_latency();
+        self.dry_latency = self.wet_latency();
+        context.set_latency_samples(self.latency as u32);
         self.dry_delay
             .iter_mut()
-            .zip(self.oversamplers.iter())
-            .for_each(|(delay, stage)| {
-                let latency = stage.get_latency_samples();
-                context.set_latency_samples(latency as u32);
-                delay.set_delay_len(latency)
-            });
+            .for_each(|delay| delay.set_delay_len(self.dry_latency));
+
+        // (re)load the impulse response from the restored state or for a new sample rate
+        self.background_sample_rate
+            .store(buffer_config.sample_rate, Ordering::Relaxed);
+        let ir_path = self.params.ir_path.read().unwrap().clone();
+        match ir_path {
+            Some(path)
+                if self.cabinet.path() != Some(path.as_str())
+                    || self.cabinet.sample_rate() != buffer_config.sample_rate =>
+            {
+                context.execute(Task::LoadImpulseResponse(path.into()))
+            }
+            None if self.cabinet.path().is_some() => context.execute(Task::ClearImpulseResponse),
+            _ => (),
+        }
+        self.cabinet_exchange.try_swap(&mut self.cabinet);
 
         self.peak_meter_decay_weight = PEAK_DECAY_FACTOR
             .powf((buffer_config.sample_rate as f64 * PEAK_METER_DECAY_MS / 1000.0).recip())
@@ -265,16 +965,7 @@ impl Plugin for NonlinearAdaa {
     }
 
     fn reset(&mut self) {
-        let new_state = State(
-            self.params.nl_proc_type.value(),
-            self.params.nl_proc_order.value(),
-        );
-
-        self.proc_state = new_state;
-
-        self.non_linear_processors
-            .iter_mut()
-            .for_each(|x| *x = NonlinearProcessor::new());
+        self.reset_processors();
 
         self.oversamplers.iter_mut().for_each(|x| x.reset());
 
@@ -287,6 +978,18 @@ impl Plugin for NonlinearAdaa {
             .for_each(|m_buff| m_buff.copy_from_slice(&[0.0; MAX_BLOCK_SIZE]));
 
         self.pre_filters.iter_mut().for_each(|x| x.reset());
+
+        self.feedback.iter_mut().for_each(|path| path.reset());
+
+        self.emphasis.reset();
+
+        self.cabinet.reset();
+
+        self.noise_gate.reset();
+
+        self.ceiling.reset();
+
+        self.test_tone.reset();
     }
 
     fn process(
@@ -295,182 +998,449 @@ impl Plugin for NonlinearAdaa {
         _aux: &mut AuxiliaryBuffers,
         context: &mut impl ProcessContext<Self>,
     ) -> ProcessStatus {
-        if !self.params.bypass.value() {
-            let current_os_factor = self.params.os_level.value();
+        // CCs override the values read here, see `MidiCcMap::value`
+        let params = self.params.clone();
+        let cc = &params.midi_map;
+
+        // the editor randomizes through the host while it is open, otherwise the targets are
+        // overridden here like CCs would
+        let randomize = cc.value(&params.randomize);
+        let mut deferred = false;
+        if randomize && !self.randomize_trigger {
+            if params.editor_state.is_open() {
+                params.randomize_requested.store(true, Ordering::Release);
+            } else if let Ok(locks) = params.randomize_locks.try_read() {
+                for (param, value) in
+                    randomize::random_values(&params, &locks, &mut self.randomize_rng)
+                {
+                    cc.override_param(param, value);
+                    // SAFETY: the randomizer only returns pointers to `params`
+                    unsafe { midi::apply_cc(param, value, self.sample_rate) };
+                }
+            } else {
+                // the editor is writing the locks, fire again on the next buffer
+                deferred = true;
+            }
+        }
+        self.randomize_trigger = randomize && !deferred;
 
-            // check os factor paramater -- if different reset oversample stages and set dry delay
-            // amount
-            self.oversamplers
-                .iter_mut()
-                .zip(self.dry_delay.iter_mut())
-                .for_each(|(os, d)| {
-                    if current_os_factor != os.get_oversample_factor() {
-                        os.set_oversample_factor(current_os_factor);
-                        d.set_delay_len(os.get_latency_samples());
-                    }
-                });
+        let current_os_factor = cc.value(&params.nonlinearity.os_level);
+        let os_factor_changed = current_os_factor != self.oversamplers[0].get_oversample_factor();
 
-            // determine current nonlinear state from user params
-            let p_state = State(
-                self.params.nl_proc_type.value(),
-                self.params.nl_proc_order.value(),
+        // check os factor paramater -- if different reset oversample stages
+        self.oversamplers.iter_mut().for_each(|os| {
+            if current_os_factor != os.get_oversample_factor() {
+                os.set_oversample_factor(current_os_factor);
+            }
+        });
+
+        if os_factor_changed {
+            self.update_oversampled_rate();
+        }
+
+        // pick up a newly loaded impulse response
+        self.cabinet_exchange.try_swap(&mut self.cabinet);
+
+        // the scope only captures while there is an editor to show it
+        let scope_enabled = self.params.editor_state.is_open();
+        let scope_oversampled =
+            scope_enabled && self.scope.capture_oversampled.load(Ordering::Relaxed);
+        if scope_enabled {
+            let os_times =
+                oversampling_factor_to_times(self.oversamplers[0].get_oversample_factor());
+            self.scope
+                .sample_rate
+                .store(self.sample_rate, Ordering::Relaxed);
+            self.scope
+                .oversampling_times
+                .store(os_times as usize, Ordering::Relaxed);
+            // the up and down sampling filters are the same length, so decimation accounts for
+            // half of the oversampling latency
+            self.scope.oversampled_lead.store(
+                self.wet_latency() - self.oversamplers[0].get_latency_samples() / 2,
+                Ordering::Relaxed,
             );
+        }
 
-            // change the nonlinear procressors if params are different
-            self.non_linear_processors.iter_mut().for_each(|nl| {
-                nl.compare_and_change_state(p_state);
-            });
+        let inject_tone = self.params.editor_state.is_open()
+            && self.params.analysis_mode.get() == AnalysisMode::Inject;
+
+        let buffer_len = buffer.samples();
+        let channels = buffer.as_slice();
+        let mut next_event = context.next_event();
+        let mut block_start = 0;
+        while block_start < buffer_len {
+            // apply the CCs due by this sample and end the block at the next one, so every CC
+            // lands on its own sample
+            let mut block_end = (block_start + MAX_BLOCK_SIZE).min(buffer_len);
+            while let Some(event) = next_event {
+                if event.timing() as usize > block_start {
+                    block_end = block_end.min(event.timing() as usize);
+                    break;
+                }
+                self.handle_event(event);
+                next_event = context.next_event();
+            }
 
-            // report latency of oversample FIR filters to DAW
-            context.set_latency_samples(self.oversamplers[0].get_latency_samples() as u32);
+            // everything below reads the parameters after this block's CCs
+            if cc.value(&params.bypass) {
+                block_start = block_end;
+                continue;
+            }
 
-            for (_, block) in buffer.iter_blocks(MAX_BLOCK_SIZE) {
-                let mut left_in_amplitude = 0.0;
-                let mut right_in_amplitude = 0.0;
-                let mut left_out_amplitude = 0.0;
-                let mut right_out_amplitude = 0.0;
+            // determine current nonlinear state from user params, the processors crossfade to it
+            // if it changed
+            let order = cc.value(&params.nonlinearity.nl_proc_order);
+            let style = cc.value(&params.nonlinearity.nl_proc_type);
+            let style_b = cc.value(&params.nonlinearity.nl_proc_type_b);
 
-                let mut block_channels = block.into_iter();
-                let left = block_channels.next().unwrap();
-                let right = block_channels.next().unwrap();
+            self.non_linear_processors
+                .iter_mut()
+                .for_each(|nl| nl.set_state(style, order));
+            self.morph_processors
+                .iter_mut()
+                .for_each(|nl| nl.set_state(style_b, order));
+
+            let style_settings = StyleSettings {
+                hysteresis: HysteresisSettings::new(
+                    cc.value(&params.nonlinearity.hysteresis_drive),
+                    cc.value(&params.nonlinearity.hysteresis_saturation),
+                    cc.value(&params.nonlinearity.hysteresis_width),
+                ),
+                diode_preset: cc.value(&params.nonlinearity.diode_preset),
+                chebyshev: params.nonlinearity.chebyshev_shape(cc),
+            };
+            self.non_linear_processors
+                .iter_mut()
+                .chain(self.morph_processors.iter_mut())
+                .for_each(|nl| nl.set_settings(&style_settings));
+            self.extra_stages
+                .iter_mut()
+                .flatten()
+                .for_each(|stage| stage.set_settings(&style_settings));
+
+            let num_extra_stages = cc.value(&params.nonlinearity.num_stages) as usize - 1;
+            self.extra_stages
+                .iter_mut()
+                .zip(self.params.nonlinearity.extra_stages())
+                .for_each(|(stage, stage_params)| {
+                    stage.iter_mut().for_each(|s| {
+                        s.set_state(cc.value(&stage_params.style), cc.value(&stage_params.order))
+                    });
+                });
+
+            let cabinet_enabled = cc.value(&params.output.cab_enable);
+            let cabinet_partitions = self
+                .cabinet
+                .partitions_for_length(cc.value(&params.output.cab_length));
 
-                let num_samples = left.len();
+            // delay the dry signal by the latency of the oversampling FIR filters and the cabinet
+            // stage, and report that plus the output ceiling's latency to the DAW
+            let dry_latency = self.wet_latency();
+            if dry_latency != self.dry_latency {
+                self.dry_latency = dry_latency;
+                self.dry_delay
+                    .iter_mut()
+                    .for_each(|d| d.set_delay_len(dry_latency));
+            }
+            self.latency = self.total_latency();
+            context.set_latency_samples(self.latency as u32);
+
+            let ceiling_mode = cc.value(&params.output.ceiling_mode);
+            let ceiling = cc.value(&params.output.ceiling);
+            let ceiling_release = self
+                .ceiling
+                .release_coeff(cc.value(&params.output.ceiling_release));
+
+            let tone_frequency = cc.value(&params.input.analysis_frequency);
+            let tone_gain = util::db_to_gain(cc.value(&params.input.analysis_level));
+
+            let gate_enabled = cc.value(&params.input.gate_enable);
+            let gate_settings = GateSettings::new(
+                self.sample_rate,
+                cc.value(&params.input.gate_threshold),
+                cc.value(&params.input.gate_hysteresis),
+                cc.value(&params.input.gate_attack),
+                cc.value(&params.input.gate_hold),
+                cc.value(&params.input.gate_release),
+                cc.value(&params.input.gate_range),
+            );
+            let mut left_in_amplitude = 0.0;
+            let mut right_in_amplitude = 0.0;
+            let mut left_out_amplitude = 0.0;
+            let mut right_out_amplitude = 0.0;
+
+            let (left_channel, right_channel) = channels.split_at_mut(1);
+            let left = &mut left_channel[0][block_start..block_end];
+            let right = &mut right_channel[0][block_start..block_end];
 
-                // to determine how many samples to process, given current oversample factor
-                let samples_to_take = 2_u32.pow(self.oversamplers[0].get_oversample_factor() as u32)
-                    as usize
-                    * num_samples;
+            let num_samples = left.len();
 
-                self.mix_scratch_buffer[0][..num_samples].copy_from_slice(left);
-                self.mix_scratch_buffer[1][..num_samples].copy_from_slice(right);
+            // to determine how many samples to process, given current oversample factor
+            let samples_to_take = 2_u32.pow(self.oversamplers[0].get_oversample_factor() as u32)
+                as usize
+                * num_samples;
 
-                // prefilter processing
+            // the analysis tone stands in for the input, dry signal included
+            if inject_tone {
                 for (l, r) in left.iter_mut().zip(right.iter_mut()) {
-                    let param_pre_filter_cutoff: &Smoother<f32> =
-                        &self.params.pre_filter_cutoff.smoothed;
-
-                    // recalculate every coefficient while smoothing
-                    if param_pre_filter_cutoff.is_smoothing() {
-                        let next_smoothed = param_pre_filter_cutoff.next();
-                        self.pre_filters.iter_mut().for_each(|f| {
-                            f.set_cutoff(next_smoothed);
-                        })
-                    }
+                    let tone = tone_gain * self.test_tone.next(tone_frequency, self.sample_rate);
+                    *l = tone;
+                    *r = tone;
+                }
+            }
 
-                    nih_dbg!(param_pre_filter_cutoff);
-                    nih_dbg!(self.pre_filters[0].get_current_cutoff());
-                    nih_dbg!(self.pre_filters[1].get_current_cutoff());
+            self.mix_scratch_buffer[0][..num_samples].copy_from_slice(left);
+            self.mix_scratch_buffer[1][..num_samples].copy_from_slice(right);
 
-                    self.pre_filters[0].process_sample(l);
-                    self.pre_filters[1].process_sample(r);
+            // gate the input ahead of the gain so hiss is not driven into the nonlinearity
+            if gate_enabled {
+                for (l, r) in left.iter_mut().zip(right.iter_mut()) {
+                    let gate_gain = self
+                        .noise_gate
+                        .process(l.abs().max(r.abs()), &gate_settings);
+                    *l *= gate_gain;
+                    *r *= gate_gain;
                 }
+            }
 
-                // delay the dry signal by the latency amount introduced in oversampling FIR
-                // filtering
-                self.dry_delay
-                    .iter_mut()
-                    .zip(self.mix_scratch_buffer.iter_mut().take(num_samples))
-                    .for_each(|(d, m)| d.delay(m));
+            // prefilter processing
+            for (l, r) in left.iter_mut().zip(right.iter_mut()) {
+                let param_pre_filter_cutoff: &Smoother<f32> =
+                    &self.params.filter.pre_filter_cutoff.smoothed;
+
+                // recalculate every coefficient while smoothing
+                if param_pre_filter_cutoff.is_smoothing() {
+                    let next_smoothed = param_pre_filter_cutoff.next();
+                    self.pre_filters.iter_mut().for_each(|f| {
+                        f.set_cutoff(next_smoothed);
+                    })
+                }
 
-                let mut left_oversample_buff = self.over_sample_process_buf[0];
-                let mut right_oversample_buff = self.over_sample_process_buf[1];
+                nih_dbg!(param_pre_filter_cutoff);
+                nih_dbg!(self.pre_filters[0].get_current_cutoff());
+                nih_dbg!(self.pre_filters[1].get_current_cutoff());
 
-                self.oversamplers[0].process_up(left, &mut left_oversample_buff);
-                self.oversamplers[1].process_up(right, &mut right_oversample_buff);
-                // nonlinear process oversampled signal
-                left_oversample_buff
-                    .iter_mut()
-                    .take(samples_to_take)
-                    .zip(right_oversample_buff.iter_mut())
-                    .for_each(|(os_l, os_r)| {
-                        let gain = self.params.gain.smoothed.next();
-                        let output = self.params.output.smoothed.next();
+                self.pre_filters[0].process_sample(l);
+                self.pre_filters[1].process_sample(r);
+            }
+
+            // delay the dry signal by the latency amount introduced in oversampling FIR
+            // filtering
+            self.dry_delay
+                .iter_mut()
+                .zip(self.mix_scratch_buffer.iter_mut())
+                .for_each(|(d, m)| d.delay(&mut m[..num_samples]));
+
+            // boost the highs going into the nonlinearity, cut them again on the way out once
+            // the oversampling latency has passed
+            let emphasis_latency = self.oversamplers[0].get_latency_samples();
+            for (l, r) in left.iter_mut().zip(right.iter_mut()) {
+                self.emphasis.set(
+                    self.params.filter.emphasis_amount.smoothed.next(),
+                    self.params.filter.emphasis_frequency.smoothed.next(),
+                    emphasis_latency,
+                );
+                (*l, *r) = self.emphasis.pre(*l, *r);
+            }
+
+            let mut left_oversample_buff = self.over_sample_process_buf[0];
+            let mut right_oversample_buff = self.over_sample_process_buf[1];
 
-                        *os_l *= gain;
-                        *os_r *= gain;
+            self.oversamplers[0].process_up(left, &mut left_oversample_buff);
+            self.oversamplers[1].process_up(right, &mut right_oversample_buff);
+            // nonlinear process oversampled signal
+            left_oversample_buff
+                .iter_mut()
+                .take(samples_to_take)
+                .zip(right_oversample_buff.iter_mut())
+                .for_each(|(os_l, os_r)| {
+                    let gain = self.params.input.gain.smoothed.next();
+                    let output = self.params.output.gain.smoothed.next();
+
+                    *os_l *= gain;
+                    *os_r *= gain;
+
+                    left_in_amplitude += (*os_l).abs();
+                    right_in_amplitude += (*os_r).abs();
+
+                    // feed the previous nonlinear output back into its input
+                    let feedback = self.params.nonlinearity.feedback.smoothed.next();
+                    let feedback_lowpass = self.params.filter.feedback_lowpass.smoothed.next();
+                    let feedback_highpass = self.params.filter.feedback_highpass.smoothed.next();
+                    self.feedback
+                        .iter_mut()
+                        .for_each(|path| path.set_cutoffs(feedback_lowpass, feedback_highpass));
+
+                    let nl_in_l = self.feedback[0].input(*os_l, feedback);
+                    let nl_in_r = self.feedback[1].input(*os_r, feedback);
+
+                    // Both slots see the same input, so blending their outputs is exactly the
+                    // ADAA of the blended curve: the antiderivatives blend linearly too. The
+                    // second slot always runs so its history is valid when the morph moves.
+                    let morph = self.params.nonlinearity.morph.smoothed.next();
+                    let a_l = self.non_linear_processors[0].process(nl_in_l);
+                    let a_r = self.non_linear_processors[1].process(nl_in_r);
+                    let b_l = self.morph_processors[0].process(nl_in_l);
+                    let b_r = self.morph_processors[1].process(nl_in_r);
+
+                    *os_l = a_l + morph * (b_l - a_l);
+                    *os_r = a_r + morph * (b_r - a_r);
+
+                    self.feedback[0].store(*os_l);
+                    self.feedback[1].store(*os_r);
+
+                    // any further gain stages, each behind its own inter-stage filters
+                    for (stage, stage_params) in self
+                        .extra_stages
+                        .iter_mut()
+                        .zip(self.params.nonlinearity.extra_stages())
+                        .take(num_extra_stages)
+                    {
+                        let drive = stage_params.drive.smoothed.next();
+                        let lowpass_cutoff = stage_params.lowpass_cutoff.smoothed.next();
+                        let highpass_cutoff = stage_params.highpass_cutoff.smoothed.next();
+
+                        stage[0].set_cutoffs(lowpass_cutoff, highpass_cutoff);
+                        stage[1].set_cutoffs(lowpass_cutoff, highpass_cutoff);
+
+                        *os_l = stage[0].process(*os_l, drive);
+                        *os_r = stage[1].process(*os_r, drive);
+                    }
 
-                        left_in_amplitude += (*os_l).abs();
-                        right_in_amplitude += (*os_r).abs();
+                    *os_l *= output;
+                    *os_r *= output;
 
-                        *os_l = self.non_linear_processors[0].process(*os_l);
-                        *os_r = self.non_linear_processors[1].process(*os_r);
+                    if scope_oversampled {
+                        self.scope.oversampled.push(0.5 * (*os_l + *os_r));
+                    }
+                });
 
-                        *os_l *= output;
-                        *os_r *= output;
-                    });
+            // down sample processed signal and store in block channel
+            self.oversamplers[0].process_down(&mut left_oversample_buff, left);
+            self.oversamplers[1].process_down(&mut right_oversample_buff, right);
 
-                // down sample processed signal and store in block channel
-                self.oversamplers[0].process_down(&mut left_oversample_buff, left);
-                self.oversamplers[1].process_down(&mut right_oversample_buff, right);
+            for (l, r) in left.iter_mut().zip(right.iter_mut()) {
+                (*l, *r) = self.emphasis.post(*l, *r);
+            }
 
-                for (l_wet, (l_dry, (r_wet, r_dry))) in left.iter_mut().zip(
+            if cabinet_enabled {
+                let trim = self
+                    .params
+                    .output
+                    .cab_trim
+                    .smoothed
+                    .next_step(num_samples as u32);
+                let mix = self
+                    .params
+                    .output
+                    .cab_mix
+                    .smoothed
+                    .next_step(num_samples as u32);
+
+                self.cabinet.process(0, left, cabinet_partitions, trim, mix);
+                self.cabinet
+                    .process(1, right, cabinet_partitions, trim, mix);
+            }
+
+            if scope_enabled {
+                for ((l, r), (l_dry, r_dry)) in left.iter().zip(right.iter()).zip(
                     self.mix_scratch_buffer[0]
                         .iter()
-                        .zip(right.iter_mut().zip(self.mix_scratch_buffer[1].iter())),
+                        .zip(self.mix_scratch_buffer[1].iter()),
                 ) {
-                    let wet_amt = self.params.dry_wet.smoothed.next();
-                    let dry_amt = 1.0 - wet_amt;
+                    self.scope.dry.push(0.5 * (l_dry + r_dry));
+                    self.scope.wet.push(0.5 * (l + r));
+                }
+            }
 
-                    *l_wet = (wet_amt * *l_wet) + (dry_amt * l_dry);
-                    *r_wet = (wet_amt * *r_wet) + (dry_amt * r_dry);
+            for (l_wet, (l_dry, (r_wet, r_dry))) in left.iter_mut().zip(
+                self.mix_scratch_buffer[0]
+                    .iter()
+                    .zip(right.iter_mut().zip(self.mix_scratch_buffer[1].iter())),
+            ) {
+                let wet_amt = self.params.output.dry_wet.smoothed.next();
+                let dry_amt = 1.0 - wet_amt;
 
-                    left_out_amplitude += (*l_wet).abs();
-                    right_out_amplitude += (*r_wet).abs();
-                }
+                *l_wet = (wet_amt * *l_wet) + (dry_amt * l_dry);
+                *r_wet = (wet_amt * *r_wet) + (dry_amt * r_dry);
+            }
 
-                // display meter levels only if GUI is open
-                if self.params.editor_state.is_open() {
-                    left_in_amplitude = left_in_amplitude / samples_to_take as f32;
-                    right_in_amplitude = right_in_amplitude / samples_to_take as f32;
-                    left_out_amplitude = left_out_amplitude / num_samples as f32;
-                    right_out_amplitude = right_out_amplitude / num_samples as f32;
-
-                    let before_left_input_meter = self.input_meters[0].load(Ordering::Relaxed);
-                    let before_right_input_meter = self.input_meters[1].load(Ordering::Relaxed);
-                    let before_left_out_meter = self.output_meters[0].load(Ordering::Relaxed);
-                    let before_right_out_meter = self.output_meters[1].load(Ordering::Relaxed);
-
-                    let new_left_input_meter = if left_in_amplitude > before_left_input_meter {
-                        left_in_amplitude.clamp(0., 10.0)
-                    } else {
-                        before_left_input_meter * self.peak_meter_decay_weight
-                            + left_in_amplitude * (1.0 - self.peak_meter_decay_weight)
-                    };
-
-                    let new_right_input_meter = if right_in_amplitude > before_right_input_meter {
-                        right_in_amplitude.clamp(0., 10.0)
-                    } else {
-                        before_right_input_meter * self.peak_meter_decay_weight
-                            + right_in_amplitude * (1.0 - self.peak_meter_decay_weight)
-                    };
-
-                    let new_left_out_meter = if left_out_amplitude > before_left_out_meter {
-                        left_out_amplitude.clamp(0., 10.0)
-                    } else {
-                        before_left_out_meter * (self.peak_meter_decay_weight)
-                            + left_out_amplitude * (1.0 - self.peak_meter_decay_weight)
-                    };
-
-                    let new_right_out_meter = if right_out_amplitude > before_right_out_meter {
-                        right_out_amplitude.clamp(0., 10.0)
-                    } else {
-                        before_right_out_meter * (self.peak_meter_decay_weight)
-                            + right_out_amplitude * (1.0 - self.peak_meter_decay_weight)
-                    };
-
-                    self.input_meters[0].store(new_left_input_meter, Ordering::Relaxed);
-                    self.input_meters[1].store(new_right_input_meter, Ordering::Relaxed);
-                    self.output_meters[0].store(new_left_out_meter, Ordering::Relaxed);
-                    self.output_meters[1].store(new_right_out_meter, Ordering::Relaxed);
-                }
+            // keep the summed output under the ceiling
+            self.ceiling
+                .process(ceiling_mode, left, right, ceiling, ceiling_release);
+
+            for (l, r) in left.iter().zip(right.iter()) {
+                left_out_amplitude += l.abs();
+                right_out_amplitude += r.abs();
             }
 
-            if !self.params.editor_state.is_open() {
-                self.input_meters[0].store(0.0, Ordering::Relaxed);
-                self.input_meters[1].store(0.0, Ordering::Relaxed);
-                self.output_meters[0].store(0.0, Ordering::Relaxed);
-                self.output_meters[1].store(0.0, Ordering::Relaxed);
+            // display meter levels only if GUI is open
+            if self.params.editor_state.is_open() {
+                left_in_amplitude = left_in_amplitude / samples_to_take as f32;
+                right_in_amplitude = right_in_amplitude / samples_to_take as f32;
+                left_out_amplitude = left_out_amplitude / num_samples as f32;
+                right_out_amplitude = right_out_amplitude / num_samples as f32;
+
+                let before_left_input_meter = self.input_meters[0].load(Ordering::Relaxed);
+                let before_right_input_meter = self.input_meters[1].load(Ordering::Relaxed);
+                let before_left_out_meter = self.output_meters[0].load(Ordering::Relaxed);
+                let before_right_out_meter = self.output_meters[1].load(Ordering::Relaxed);
+
+                let new_left_input_meter = if left_in_amplitude > before_left_input_meter {
+                    left_in_amplitude.clamp(0., 10.0)
+                } else {
+                    before_left_input_meter * self.peak_meter_decay_weight
+                        + left_in_amplitude * (1.0 - self.peak_meter_decay_weight)
+                };
+
+                let new_right_input_meter = if right_in_amplitude > before_right_input_meter {
+                    right_in_amplitude.clamp(0., 10.0)
+                } else {
+                    before_right_input_meter * self.peak_meter_decay_weight
+                        + right_in_amplitude * (1.0 - self.peak_meter_decay_weight)
+                };
+
+                let new_left_out_meter = if left_out_amplitude > before_left_out_meter {
+                    left_out_amplitude.clamp(0., 10.0)
+                } else {
+                    before_left_out_meter * (self.peak_meter_decay_weight)
+                        + left_out_amplitude * (1.0 - self.peak_meter_decay_weight)
+                };
+
+                let new_right_out_meter = if right_out_amplitude > before_right_out_meter {
+                    right_out_amplitude.clamp(0., 10.0)
+                } else {
+                    before_right_out_meter * (self.peak_meter_decay_weight)
+                        + right_out_amplitude * (1.0 - self.peak_meter_decay_weight)
+                };
+
+                self.input_meters[0].store(new_left_input_meter, Ordering::Relaxed);
+                self.input_meters[1].store(new_right_input_meter, Ordering::Relaxed);
+                self.output_meters[0].store(new_left_out_meter, Ordering::Relaxed);
+                self.output_meters[1].store(new_right_out_meter, Ordering::Relaxed);
+
+                let gate_gain = if gate_enabled {
+                    self.noise_gate.gain()
+                } else {
+                    1.0
+                };
+                self.gate_meter.store(gate_gain, Ordering::Relaxed);
             }
+
+            block_start = block_end;
+        }
+
+        while let Some(event) = next_event {
+            self.handle_event(event);
+            next_event = context.next_event();
+        }
+
+        if !self.params.editor_state.is_open() {
+            self.input_meters[0].store(0.0, Ordering::Relaxed);
+            self.input_meters[1].store(0.0, Ordering::Relaxed);
+            self.output_meters[0].store(0.0, Ordering::Relaxed);
+            self.output_meters[1].store(0.0, Ordering::Relaxed);
         }
 
         ProcessStatus::Normal
@@ -485,6 +1455,85 @@ impl ClapPlugin for NonlinearAdaa {
 
     // Don't forget to change these features
     const CLAP_FEATURES: &'static [ClapFeature] = &[ClapFeature::AudioEffect, ClapFeature::Stereo];
+
+    // Controllers show the first page by default, so it gathers the controls used most. The other
+    // pages follow the parameter groups.
+    fn remote_controls(&self, context: &mut impl RemoteControlsContext) {
+        let params = &self.params;
+
+        context.add_section("Main", |section| {
+            section.add_page("Main", |page| {
+                page.add_param(&params.input.gain);
+                page.add_param(&params.nonlinearity.nl_proc_type);
+                page.add_param(&params.nonlinearity.morph);
+                page.add_param(&params.nonlinearity.nl_proc_order);
+                page.add_param(&params.filter.pre_filter_cutoff);
+                page.add_param(&params.filter.emphasis_amount);
+                page.add_param(&params.output.gain);
+                page.add_param(&params.output.dry_wet);
+            });
+        });
+
+        context.add_section("Input", |section| {
+            section.add_page("Gate", |page| {
+                page.add_param(&params.input.gain);
+                page.add_param(&params.input.gate_enable);
+                page.add_param(&params.input.gate_threshold);
+                page.add_param(&params.input.gate_hysteresis);
+                page.add_param(&params.input.gate_attack);
+                page.add_param(&params.input.gate_hold);
+                page.add_param(&params.input.gate_release);
+                page.add_param(&params.input.gate_range);
+            });
+        });
+
+        context.add_section("Nonlinearity", |section| {
+            section.add_page("Style", |page| {
+                page.add_param(&params.nonlinearity.nl_proc_type);
+                page.add_param(&params.nonlinearity.nl_proc_type_b);
+                page.add_param(&params.nonlinearity.morph);
+                page.add_param(&params.nonlinearity.nl_proc_order);
+                page.add_param(&params.nonlinearity.os_level);
+                page.add_param(&params.nonlinearity.feedback);
+                page.add_param(&params.nonlinearity.num_stages);
+            });
+            section.add_page("Character", |page| {
+                page.add_param(&params.nonlinearity.hysteresis_drive);
+                page.add_param(&params.nonlinearity.hysteresis_saturation);
+                page.add_param(&params.nonlinearity.hysteresis_width);
+                page.add_param(&params.nonlinearity.diode_preset);
+            });
+            section.add_page("Stages", |page| {
+                for stage in params.nonlinearity.extra_stages() {
+                    page.add_param(&stage.style);
+                    page.add_param(&stage.drive);
+                }
+            });
+        });
+
+        context.add_section("Filter", |section| {
+            section.add_page("Filter", |page| {
+                page.add_param(&params.filter.pre_filter_cutoff);
+                page.add_param(&params.filter.emphasis_amount);
+                page.add_param(&params.filter.emphasis_frequency);
+                page.add_param(&params.filter.feedback_lowpass);
+                page.add_param(&params.filter.feedback_highpass);
+            });
+        });
+
+        context.add_section("Output", |section| {
+            section.add_page("Output", |page| {
+                page.add_param(&params.output.gain);
+                page.add_param(&params.output.dry_wet);
+                page.add_param(&params.output.cab_enable);
+                page.add_param(&params.output.cab_mix);
+                page.add_param(&params.output.ceiling_mode);
+                page.add_param(&params.output.ceiling);
+                page.add_param(&params.output.ceiling_release);
+                page.add_param(&params.bypass);
+            });
+        });
+    }
 }
 
 impl Vst3Plugin for NonlinearAdaa {
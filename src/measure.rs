@@ -0,0 +1,179 @@
+//! Offline measurement of aliasing introduced by the nonlinear stage.
+//!
+//! A bin-centred sine is run through the same upsample -> drive -> nonlinearity -> downsample
+//! chain the plugin uses, and the spectrum of the result is split into energy that sits on a
+//! harmonic of the input (wanted distortion) and everything else above DC (aliasing that folded
+//! back from above the oversampled Nyquist frequency).
+//!
+//! Only built with the `measure` feature. The live analysis the editor uses is re-exported here
+//! so the measurement tests can check it against known signals.
+
+use std::f64::consts::PI;
+
+use jdsp::{AntiderivativeOrder, NonlinearProcessor, ProcessorState::State, ProcessorStyle};
+use jdsp::{Oversample, OversampleFactor};
+use nih_plug::prelude::Enum;
+
+use crate::analysis::windowed_power_spectrum;
+pub use crate::analysis::{harmonic_analysis, HarmonicAnalysis, ANALYSIS_HARMONICS, FFT_SIZE};
+
+pub const MEASURE_SAMPLE_RATE: f32 = 44100.0;
+
+const BLOCK_SIZE: usize = 32;
+// samples processed before the analysed window so the FIR stages and ADAA history have settled
+const SETTLE_SAMPLES: usize = 4096;
+// half width of the Hann main lobe, in bins, counted as belonging to a harmonic
+const HARMONIC_BIN_SPREAD: usize = 2;
+
+/// Test tones used for single-frequency measurements, in Hz.
+pub const TEST_TONES: [f32; 4] = [2000.0, 5000.0, 10000.0, 15000.0];
+
+/// Endpoints and number of steps of the stepped sine sweep.
+pub const SWEEP_START_HZ: f32 = 1000.0;
+pub const SWEEP_END_HZ: f32 = 16000.0;
+pub const SWEEP_STEPS: usize = 12;
+
+#[derive(Clone, Copy)]
+pub struct ChainConfig {
+    pub style: ProcessorStyle,
+    pub order: AntiderivativeOrder,
+    pub factor: OversampleFactor,
+    pub drive: f32,
+}
+
+impl ChainConfig {
+    /// Every style, order and oversampling factor combination at the given drive, keyed as
+    /// `style/order/factor` using the parameter display names.
+    pub fn all(drive: f32) -> Vec<(String, ChainConfig)> {
+        let mut configs = Vec::new();
+
+        for (s_idx, s_name) in ProcessorStyle::variants().iter().enumerate() {
+            for (o_idx, o_name) in AntiderivativeOrder::variants().iter().enumerate() {
+                for (f_idx, f_name) in OversampleFactor::variants().iter().enumerate() {
+                    configs.push((
+                        format!("{s_name}/{o_name}/{f_name}"),
+                        ChainConfig {
+                            style: ProcessorStyle::from_index(s_idx),
+                            order: AntiderivativeOrder::from_index(o_idx),
+                            factor: OversampleFactor::from_index(f_idx),
+                            drive,
+                        },
+                    ));
+                }
+            }
+        }
+
+        configs
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default)]
+pub struct AliasMeasurement {
+    pub harmonic_energy: f64,
+    pub alias_energy: f64,
+}
+
+impl AliasMeasurement {
+    /// Signal-to-alias ratio in dB. Higher is better.
+    pub fn signal_to_alias_db(&self) -> f64 {
+        10.0 * (self.harmonic_energy / self.alias_energy.max(f64::MIN_POSITIVE)).log10()
+    }
+}
+
+/// Rounds `freq` to the nearest odd FFT bin. An odd bin shares no factor with the power of two
+/// FFT size, so folded harmonics can never land on an unfolded harmonic.
+pub fn fundamental_bin(freq: f32) -> usize {
+    let bin = (freq as f64 * FFT_SIZE as f64 / MEASURE_SAMPLE_RATE as f64).round() as usize;
+    bin | 1
+}
+
+pub fn bin_to_hz(bin: usize) -> f32 {
+    bin as f32 * MEASURE_SAMPLE_RATE / FFT_SIZE as f32
+}
+
+pub fn bin_centred_sine(bin: usize, num_samples: usize) -> Vec<f32> {
+    (0..num_samples)
+        .map(|n| (2.0 * PI * bin as f64 * n as f64 / FFT_SIZE as f64).sin() as f32)
+        .collect()
+}
+
+/// Runs `input` through the oversampled nonlinear chain in plugin sized blocks.
+pub fn process_chain(config: &ChainConfig, input: &[f32]) -> Vec<f32> {
+    process_chain_in_blocks(config, input, BLOCK_SIZE)
+}
+
+/// Runs `input` through the oversampled nonlinear chain in blocks of `block_size` samples.
+pub fn process_chain_in_blocks(config: &ChainConfig, input: &[f32], block_size: usize) -> Vec<f32> {
+    let os_times = 2_usize.pow(config.factor as u32);
+
+    let mut os = Oversample::new(config.factor, block_size);
+    os.initialize_oversample_stages();
+    let mut nl = NonlinearProcessor::new();
+    nl.compare_and_change_state(State(config.style, config.order));
+
+    let mut block = vec![0.0_f32; block_size];
+    let mut up = vec![0.0_f32; block_size * os_times];
+    let mut output = Vec::with_capacity(input.len());
+
+    for chunk in input.chunks(block_size) {
+        let block = &mut block[..chunk.len()];
+        block.copy_from_slice(chunk);
+
+        os.process_up(block, &mut up);
+
+        up.iter_mut()
+            .take(chunk.len() * os_times)
+            .for_each(|v| *v = nl.process(*v * config.drive));
+
+        os.process_down(&mut up, block);
+        output.extend_from_slice(block);
+    }
+
+    output
+}
+
+/// Hann windowed power spectrum of the first `FFT_SIZE` samples of `signal`, bins `0..=N/2`.
+pub fn power_spectrum(signal: &[f32]) -> Vec<f64> {
+    windowed_power_spectrum(signal, |phase| 0.5 - 0.5 * phase.cos())
+}
+
+/// Splits `spectrum` into energy on harmonics of `fundamental_bin` and the remaining energy
+/// above DC.
+pub fn alias_measurement(spectrum: &[f64], fundamental_bin: usize) -> AliasMeasurement {
+    let near_harmonic = |bin: usize| {
+        let nearest = ((bin as f64 / fundamental_bin as f64).round() as usize).max(1);
+        bin.abs_diff(nearest * fundamental_bin) <= HARMONIC_BIN_SPREAD
+    };
+
+    spectrum
+        .iter()
+        .enumerate()
+        .skip(HARMONIC_BIN_SPREAD + 1)
+        .fold(AliasMeasurement::default(), |mut acc, (bin, power)| {
+            if near_harmonic(bin) {
+                acc.harmonic_energy += power;
+            } else {
+                acc.alias_energy += power;
+            }
+            acc
+        })
+}
+
+/// Measures the signal-to-alias ratio of `config` for a single test tone.
+pub fn measure_tone(config: &ChainConfig, freq: f32) -> AliasMeasurement {
+    let bin = fundamental_bin(freq);
+    let input = bin_centred_sine(bin, SETTLE_SAMPLES + FFT_SIZE);
+    let output = process_chain(config, &input);
+
+    alias_measurement(&power_spectrum(&output[SETTLE_SAMPLES..]), bin)
+}
+
+/// Stepped, logarithmically spaced sine sweep. Returns the worst measurement along the sweep.
+pub fn measure_sweep(config: &ChainConfig) -> AliasMeasurement {
+    let ratio = (SWEEP_END_HZ / SWEEP_START_HZ).powf(1.0 / (SWEEP_STEPS - 1) as f32);
+
+    (0..SWEEP_STEPS)
+        .map(|step| measure_tone(config, SWEEP_START_HZ * ratio.powi(step as i32)))
+        .min_by(|a, b| a.signal_to_alias_db().total_cmp(&b.signal_to_alias_db()))
+        .unwrap_or_default()
+}
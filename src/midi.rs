@@ -0,0 +1,319 @@
+//! MIDI CC control of parameters, with MIDI learn from the editor.
+//!
+//! nih-plug has no way for the audio thread to change a parameter, so a CC drives the
+//! parameter's smoother directly and overrides the value the audio thread reads with
+//! [`MidiCcMap::value`]. The plugin splits its blocks at every CC and reads parameters per block,
+//! so either way the change lands on the CC's sample. Only the oversampling factor, which
+//! changes the latency, waits for the next buffer. The value is also queued for the editor to
+//! send to the host through its GUI context, so the host's parameter, and with it the saved
+//! state, catches up once the editor is open.
+
+use nih_plug::params::persist::PersistentField;
+use nih_plug::prelude::*;
+use nih_plug_vizia::vizia::prelude::Context;
+use std::collections::{BTreeMap, HashMap};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{Arc, OnceLock, Weak};
+use std::time::Duration;
+
+use crate::editor;
+use crate::NonlinearAdaaParams;
+
+const NUM_CCS: usize = 128;
+const NONE: usize = usize::MAX;
+const SYNC_INTERVAL: Duration = Duration::from_millis(20);
+
+/// Which parameter each CC controls. Saved as CC number to parameter id, so assignments survive
+/// parameters being added or reordered.
+#[derive(Debug)]
+pub struct MidiCcMap {
+    // index into `params` for each CC
+    targets: [AtomicUsize; NUM_CCS],
+    // the parameter waiting for a controller
+    learning: AtomicUsize,
+    params: OnceLock<Vec<(String, ParamPtr)>>,
+    // index into `params` of each parameter
+    slots: OnceLock<HashMap<ParamPtr, usize>>,
+    // latest CC value of each parameter, NaN once the editor has passed it on
+    pending: OnceLock<Vec<AtomicF32>>,
+    // the value the audio thread reads in place of each parameter's own, see `value`
+    overrides: OnceLock<Vec<CcOverride>>,
+    // set once any CC has moved a parameter, so unmapped sessions skip the lookup
+    overriding: AtomicBool,
+    // set whenever `pending` or an assignment changes
+    dirty: AtomicBool,
+}
+
+impl Default for MidiCcMap {
+    fn default() -> Self {
+        Self {
+            targets: std::array::from_fn(|_| AtomicUsize::new(NONE)),
+            learning: AtomicUsize::new(NONE),
+            params: OnceLock::new(),
+            slots: OnceLock::new(),
+            pending: OnceLock::new(),
+            overrides: OnceLock::new(),
+            overriding: AtomicBool::new(false),
+            dirty: AtomicBool::new(false),
+        }
+    }
+}
+
+/// A CC value and the normalized value the parameter had when it arrived. The CC value stands
+/// for as long as the parameter keeps that value, the host setting it ends the override.
+#[derive(Debug)]
+struct CcOverride {
+    value: AtomicF32,
+    base: AtomicF32,
+}
+
+impl Default for CcOverride {
+    fn default() -> Self {
+        Self {
+            value: AtomicF32::new(f32::NAN),
+            base: AtomicF32::new(f32::NAN),
+        }
+    }
+}
+
+impl MidiCcMap {
+    /// Makes every parameter in `param_map` available for MIDI control. Called once, right
+    /// after the parameters are created.
+    pub fn set_params(&self, param_map: Vec<(String, ParamPtr, String)>) {
+        let params: Vec<(String, ParamPtr)> = param_map
+            .into_iter()
+            .map(|(id, ptr, _)| (id, ptr))
+            .collect();
+        let slots = params
+            .iter()
+            .enumerate()
+            .map(|(slot, (_, ptr))| (*ptr, slot))
+            .collect();
+        let pending = params.iter().map(|_| AtomicF32::new(f32::NAN)).collect();
+        let overrides = params.iter().map(|_| CcOverride::default()).collect();
+
+        let _ = self.params.set(params);
+        let _ = self.slots.set(slots);
+        let _ = self.pending.set(pending);
+        let _ = self.overrides.set(overrides);
+    }
+
+    fn params(&self) -> &[(String, ParamPtr)] {
+        self.params.get().map_or(&[], Vec::as_slice)
+    }
+
+    fn slot(&self, param: ParamPtr) -> Option<usize> {
+        self.slots.get()?.get(&param).copied()
+    }
+
+    fn assign_ids(&self, assignments: &BTreeMap<u8, String>) {
+        self.targets
+            .iter()
+            .for_each(|target| target.store(NONE, Ordering::Relaxed));
+        for (cc, id) in assignments {
+            // ids that no longer exist are dropped
+            if let Some(slot) = self
+                .params()
+                .iter()
+                .position(|(param_id, _)| param_id == id)
+            {
+                if let Some(target) = self.targets.get(*cc as usize) {
+                    target.store(slot, Ordering::Relaxed);
+                }
+            }
+        }
+        self.dirty.store(true, Ordering::Release);
+    }
+
+    /// Waits for the next CC to control `param`.
+    pub fn learn(&self, param: ParamPtr) {
+        if let Some(slot) = self.slot(param) {
+            self.learning.store(slot, Ordering::Release);
+        }
+    }
+
+    pub fn cancel_learn(&self) {
+        self.learning.store(NONE, Ordering::Release);
+    }
+
+    pub fn learning(&self) -> Option<ParamPtr> {
+        let slot = self.learning.load(Ordering::Acquire);
+        self.params().get(slot).map(|(_, ptr)| *ptr)
+    }
+
+    /// Removes every CC assigned to `param`.
+    pub fn forget(&self, param: ParamPtr) {
+        if let Some(slot) = self.slot(param) {
+            self.targets.iter().for_each(|target| {
+                let _ = target.compare_exchange(slot, NONE, Ordering::AcqRel, Ordering::Relaxed);
+            });
+            self.dirty.store(true, Ordering::Release);
+        }
+    }
+
+    /// The CC controlling `param`, if any.
+    pub fn assigned_cc(&self, param: ParamPtr) -> Option<u8> {
+        let slot = self.slot(param)?;
+        self.targets
+            .iter()
+            .position(|target| target.load(Ordering::Relaxed) == slot)
+            .map(|cc| cc as u8)
+    }
+
+    /// Handles a CC on the audio thread, completing a MIDI learn if one is waiting. Returns the
+    /// parameter it controls and queues the value for the editor.
+    pub fn handle_cc(&self, cc: u8, value: f32) -> Option<ParamPtr> {
+        let cc = cc as usize;
+        if cc >= NUM_CCS {
+            return None;
+        }
+
+        let learning = self.learning.swap(NONE, Ordering::AcqRel);
+        if learning != NONE {
+            // a parameter follows one controller at a time
+            self.targets.iter().for_each(|target| {
+                let _ =
+                    target.compare_exchange(learning, NONE, Ordering::AcqRel, Ordering::Relaxed);
+            });
+            self.targets[cc].store(learning, Ordering::Release);
+            self.dirty.store(true, Ordering::Release);
+        }
+
+        let slot = self.targets[cc].load(Ordering::Acquire);
+        let (_, param) = self.params().get(slot)?;
+        self.override_slot(slot, *param, value);
+
+        Some(*param)
+    }
+
+    /// Overrides `param` with `normalized` on the audio thread, as a CC would, and queues the
+    /// value for the editor. The caller moves the parameter's smoother with [`apply_cc`].
+    pub fn override_param(&self, param: ParamPtr, normalized: f32) {
+        if let Some(slot) = self.slot(param) {
+            self.override_slot(slot, param, normalized);
+        }
+    }
+
+    fn override_slot(&self, slot: usize, param: ParamPtr, normalized: f32) {
+        if let Some(pending) = self.pending.get().and_then(|pending| pending.get(slot)) {
+            pending.store(normalized, Ordering::Relaxed);
+            self.dirty.store(true, Ordering::Release);
+        }
+        if let Some(cc_override) = self
+            .overrides
+            .get()
+            .and_then(|overrides| overrides.get(slot))
+        {
+            // SAFETY: the map only holds pointers to the parameters it is part of
+            let base = unsafe { param.unmodulated_normalized_value() };
+            cc_override.base.store(base, Ordering::Relaxed);
+            cc_override.value.store(normalized, Ordering::Relaxed);
+            self.overriding.store(true, Ordering::Relaxed);
+        }
+    }
+
+    /// `param`'s value on the audio thread: the last CC value if a CC has moved it since the host
+    /// last set it, otherwise the parameter's own value.
+    pub fn value<P: Param>(&self, param: &P) -> P::Plain {
+        if self.overriding.load(Ordering::Relaxed) {
+            let ptr = param.as_ptr();
+            let cc_override = self
+                .slot(ptr)
+                .and_then(|slot| self.overrides.get()?.get(slot));
+            if let Some(cc_override) = cc_override {
+                let value = cc_override.value.load(Ordering::Relaxed);
+                if !value.is_nan()
+                    && cc_override.base.load(Ordering::Relaxed)
+                        == param.unmodulated_normalized_value()
+                {
+                    return param.preview_plain(value);
+                }
+            }
+        }
+
+        param.modulated_plain_value()
+    }
+
+    /// Whether anything changed since the last call.
+    pub fn take_dirty(&self) -> bool {
+        self.dirty.swap(false, Ordering::AcqRel)
+    }
+
+    /// Calls `f` with each parameter and normalized value CCs have set since the last call.
+    pub fn drain_pending(&self, mut f: impl FnMut(ParamPtr, f32)) {
+        let Some(pending) = self.pending.get() else {
+            return;
+        };
+
+        for ((_, param), value) in self.params().iter().zip(pending) {
+            let value = value.swap(f32::NAN, Ordering::Relaxed);
+            if !value.is_nan() {
+                f(*param, value);
+            }
+        }
+    }
+}
+
+impl<'a> PersistentField<'a, BTreeMap<u8, String>> for MidiCcMap {
+    fn set(&self, new_value: BTreeMap<u8, String>) {
+        self.assign_ids(&new_value);
+    }
+
+    fn map<F, R>(&self, f: F) -> R
+    where
+        F: Fn(&BTreeMap<u8, String>) -> R,
+    {
+        let assignments = self
+            .targets
+            .iter()
+            .enumerate()
+            .filter_map(|(cc, target)| {
+                let (id, _) = self.params().get(target.load(Ordering::Relaxed))?;
+                Some((cc as u8, id.clone()))
+            })
+            .collect();
+
+        f(&assignments)
+    }
+}
+
+/// Moves `param` to `normalized` on the audio thread, through its smoother so the change lands
+/// on this sample. Bool and enum parameters have no smoother, they follow the CC through
+/// [`MidiCcMap::value`] once the plugin reads them for the next block, which starts at the CC.
+///
+/// # Safety
+///
+/// `param` must point to a parameter that outlives the call.
+pub unsafe fn apply_cc(param: ParamPtr, normalized: f32, sample_rate: f32) {
+    match param {
+        ParamPtr::FloatParam(param) => {
+            let param = &*param;
+            param
+                .smoothed
+                .set_target(sample_rate, param.preview_plain(normalized));
+        }
+        ParamPtr::IntParam(param) => {
+            let param = &*param;
+            param
+                .smoothed
+                .set_target(sample_rate, param.preview_plain(normalized));
+        }
+        ParamPtr::BoolParam(_) | ParamPtr::EnumParam(_) => (),
+    }
+}
+
+/// Emits `event()` whenever CCs have moved parameters or changed assignments, checking every
+/// [`SYNC_INTERVAL`].
+pub fn watch_cc_map<E, F>(
+    cx: &mut Context,
+    params: Arc<NonlinearAdaaParams>,
+    alive: Weak<()>,
+    event: F,
+) where
+    E: Send + 'static,
+    F: Fn() -> E + Send + 'static,
+{
+    editor::poll_while_open(cx, alive, SYNC_INTERVAL, move || {
+        params.midi_map.take_dirty().then(&event)
+    });
+}
@@ -0,0 +1,188 @@
+//! Randomization of the main controls, for exploring quickly. Each target is drawn from a range
+//! that stays musically useful rather than from the parameter's full range, and targets can be
+//! locked to keep them as they are.
+//!
+//! The editor randomizes through the host as one undo step. While the editor is closed the
+//! randomize trigger is handled on the audio thread, which moves the targets the way a MIDI CC
+//! does, and the host catches up once the editor opens.
+
+use jdsp::AntiderivativeOrder;
+use nih_plug::prelude::*;
+use nih_plug_vizia::vizia::prelude::Context;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeSet;
+use std::ops::Range;
+use std::sync::atomic::Ordering;
+use std::sync::{Arc, Weak};
+use std::time::{Duration, SystemTime};
+
+use crate::editor;
+use crate::styles::ClipStyle;
+use crate::undo::ParamChange;
+use crate::NonlinearAdaaParams;
+
+/// Input gain, in dB. Past this most styles are a square wave.
+pub const DRIVE_DB_RANGE: Range<f32> = 0.0..24.0;
+/// Prefilter cutoff, in Hz, drawn evenly in octaves.
+pub const PREFILTER_HZ_RANGE: Range<f32> = 1500.0..20000.0;
+/// Wet share of the dry/wet mix.
+pub const MIX_RANGE: Range<f32> = 0.5..1.0;
+
+const WATCH_INTERVAL: Duration = Duration::from_millis(20);
+
+/// A control the randomizer changes.
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq, PartialOrd, Ord, Clone, Copy)]
+pub enum RandomizeTarget {
+    Style,
+    Order,
+    Drive,
+    Prefilter,
+    Mix,
+}
+
+impl RandomizeTarget {
+    pub const ALL: [RandomizeTarget; 5] = [
+        RandomizeTarget::Style,
+        RandomizeTarget::Order,
+        RandomizeTarget::Drive,
+        RandomizeTarget::Prefilter,
+        RandomizeTarget::Mix,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            RandomizeTarget::Style => "Style",
+            RandomizeTarget::Order => "Order",
+            RandomizeTarget::Drive => "Drive",
+            RandomizeTarget::Prefilter => "Prefilter",
+            RandomizeTarget::Mix => "Mix",
+        }
+    }
+
+    /// A random plain value for the target. Styles and orders are variant indices.
+    pub fn random_plain(self, rng: &mut Rng) -> f32 {
+        match self {
+            RandomizeTarget::Style => rng.index(ClipStyle::variants().len()) as f32,
+            RandomizeTarget::Order => rng.index(AntiderivativeOrder::variants().len()) as f32,
+            RandomizeTarget::Drive => rng.range(DRIVE_DB_RANGE),
+            RandomizeTarget::Prefilter => rng
+                .range(PREFILTER_HZ_RANGE.start.log2()..PREFILTER_HZ_RANGE.end.log2())
+                .exp2(),
+            RandomizeTarget::Mix => rng.range(MIX_RANGE),
+        }
+    }
+
+    /// The parameter the target changes and its normalized value for `plain`.
+    fn param(self, params: &NonlinearAdaaParams, plain: f32) -> (ParamPtr, f32) {
+        match self {
+            RandomizeTarget::Style => {
+                let param = &params.nonlinearity.nl_proc_type;
+                let style = ClipStyle::from_index(plain as usize);
+                (param.as_ptr(), param.preview_normalized(style))
+            }
+            RandomizeTarget::Order => {
+                let param = &params.nonlinearity.nl_proc_order;
+                let order = AntiderivativeOrder::from_index(plain as usize);
+                (param.as_ptr(), param.preview_normalized(order))
+            }
+            RandomizeTarget::Drive => {
+                let param = &params.input.gain;
+                (
+                    param.as_ptr(),
+                    param.preview_normalized(util::db_to_gain(plain)),
+                )
+            }
+            RandomizeTarget::Prefilter => {
+                let param = &params.filter.pre_filter_cutoff;
+                (param.as_ptr(), param.preview_normalized(plain))
+            }
+            RandomizeTarget::Mix => {
+                let param = &params.output.dry_wet;
+                (param.as_ptr(), param.preview_normalized(plain))
+            }
+        }
+    }
+}
+
+/// Xorshift generator, plenty for picking settings.
+pub struct Rng(u32);
+
+impl Rng {
+    pub fn new(seed: u32) -> Self {
+        // xorshift never leaves zero
+        Self(seed.max(1))
+    }
+
+    /// Seeded from the clock, for a different result every time.
+    pub fn from_time() -> Self {
+        let nanos = SystemTime::now()
+            .duration_since(SystemTime::UNIX_EPOCH)
+            .map_or(1, |time| time.subsec_nanos());
+        Self::new(nanos)
+    }
+
+    /// Uniform in `[0, 1)`.
+    pub fn next_f32(&mut self) -> f32 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 17;
+        self.0 ^= self.0 << 5;
+        (self.0 >> 8) as f32 / (1 << 24) as f32
+    }
+
+    pub fn range(&mut self, range: Range<f32>) -> f32 {
+        range.start + self.next_f32() * (range.end - range.start)
+    }
+
+    /// Uniform in `0..len`.
+    pub fn index(&mut self, len: usize) -> usize {
+        ((self.next_f32() * len as f32) as usize).min(len - 1)
+    }
+}
+
+/// New normalized values for every target that is not locked. Allocates nothing, so the audio
+/// thread can randomize too.
+pub fn random_values<'a>(
+    params: &'a NonlinearAdaaParams,
+    locks: &'a BTreeSet<RandomizeTarget>,
+    rng: &'a mut Rng,
+) -> impl Iterator<Item = (ParamPtr, f32)> + 'a {
+    RandomizeTarget::ALL
+        .into_iter()
+        .filter(|target| !locks.contains(target))
+        .map(|target| target.param(params, target.random_plain(rng)))
+}
+
+/// The editor's randomization as changes from the current values, recorded as one undo step.
+pub fn random_changes(
+    params: &NonlinearAdaaParams,
+    locks: &BTreeSet<RandomizeTarget>,
+    rng: &mut Rng,
+) -> Vec<ParamChange> {
+    random_values(params, locks, rng)
+        .map(|(param, after)| ParamChange {
+            param,
+            // SAFETY: the randomizer only returns pointers to `params`
+            before: unsafe { param.unmodulated_normalized_value() },
+            after,
+        })
+        .collect()
+}
+
+/// Emits `event()` whenever the randomize trigger fires while the editor is open, checking every
+/// [`WATCH_INTERVAL`].
+pub(crate) fn watch_trigger<E, F>(
+    cx: &mut Context,
+    params: Arc<NonlinearAdaaParams>,
+    alive: Weak<()>,
+    event: F,
+) where
+    E: Send + 'static,
+    F: Fn() -> E + Send + 'static,
+{
+    editor::poll_while_open(cx, alive, WATCH_INTERVAL, move || {
+        params
+            .randomize_requested
+            .swap(false, Ordering::AcqRel)
+            .then(&event)
+    });
+}
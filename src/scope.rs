@@ -0,0 +1,78 @@
+//! Sample capture for the editor's oscilloscope. The audio thread writes into fixed size rings of
+//! atomics and never waits on the editor; the editor copies out the newest samples when it draws.
+//! A read can overlap a write, which at worst shows a few samples from the next block.
+
+use nih_plug::prelude::AtomicF32;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+
+/// Samples kept per trace, a little over a second at 44.1 kHz and 46 ms of 16x oversampled audio.
+pub const SCOPE_CAPACITY: usize = 1 << 16;
+const MASK: usize = SCOPE_CAPACITY - 1;
+
+/// Single writer ring of samples.
+pub struct ScopeBuffer {
+    samples: Box<[AtomicF32]>,
+    // total number of samples written, wraps around at usize::MAX
+    written: AtomicUsize,
+}
+
+impl Default for ScopeBuffer {
+    fn default() -> Self {
+        Self {
+            samples: (0..SCOPE_CAPACITY).map(|_| AtomicF32::new(0.0)).collect(),
+            written: AtomicUsize::new(0),
+        }
+    }
+}
+
+impl ScopeBuffer {
+    /// Appends a sample. Only the audio thread may call this.
+    #[inline]
+    pub fn push(&self, sample: f32) {
+        let written = self.written.load(Ordering::Relaxed);
+        self.samples[written & MASK].store(sample, Ordering::Relaxed);
+        self.written
+            .store(written.wrapping_add(1), Ordering::Release);
+    }
+
+    /// Fills `out` with the newest samples, oldest first.
+    pub fn snapshot(&self, out: &mut [f32]) {
+        let written = self.written.load(Ordering::Acquire);
+        let len = out.len().min(SCOPE_CAPACITY);
+        let start = written.wrapping_sub(len);
+        out[..len].iter_mut().enumerate().for_each(|(i, sample)| {
+            *sample = self.samples[start.wrapping_add(i) & MASK].load(Ordering::Relaxed)
+        });
+    }
+}
+
+/// Everything the oscilloscope shows, shared between the plugin and the editor.
+pub struct Scope {
+    /// The latency compensated dry input, in step with `wet`.
+    pub dry: ScopeBuffer,
+    /// The processed signal ahead of the dry/wet mix.
+    pub wet: ScopeBuffer,
+    /// The nonlinearity's output at the oversampled rate, ahead of decimation. Only captured
+    /// while `capture_oversampled` is set.
+    pub oversampled: ScopeBuffer,
+    pub capture_oversampled: AtomicBool,
+    pub sample_rate: AtomicF32,
+    pub oversampling_times: AtomicUsize,
+    /// Samples at the base rate by which `oversampled` runs ahead of `wet`: the decimation
+    /// filter's share of the oversampling latency plus the cabinet's.
+    pub oversampled_lead: AtomicUsize,
+}
+
+impl Default for Scope {
+    fn default() -> Self {
+        Self {
+            dry: ScopeBuffer::default(),
+            wet: ScopeBuffer::default(),
+            oversampled: ScopeBuffer::default(),
+            capture_oversampled: AtomicBool::new(false),
+            sample_rate: AtomicF32::new(44100.0),
+            oversampling_times: AtomicUsize::new(2),
+            oversampled_lead: AtomicUsize::new(0),
+        }
+    }
+}
@@ -0,0 +1,160 @@
+use jdsp::AntiderivativeOrder;
+use nih_plug::prelude::*;
+use std::sync::Arc;
+
+use crate::crossfade::CrossfadingProcessor;
+use crate::filters::OnePole;
+use crate::styles::{ClipStyle, StyleSettings};
+
+/// Stages that can follow the main nonlinearity, so up to four stages in total.
+pub const MAX_EXTRA_STAGES: usize = 3;
+
+const STAGE_LOWPASS_DEFAULT: f32 = 20000.0;
+const STAGE_HIGHPASS_DEFAULT: f32 = 10.0;
+
+/// Settings for one additional gain stage. The filters sit in front of the stage's drive, between
+/// it and the previous stage.
+#[derive(Params, Debug)]
+pub struct StageParams {
+    #[id = "style"]
+    pub style: EnumParam<ClipStyle>,
+    #[id = "order"]
+    pub order: EnumParam<AntiderivativeOrder>,
+    #[id = "drive"]
+    pub drive: FloatParam,
+    #[id = "lowpass"]
+    pub lowpass_cutoff: FloatParam,
+    #[id = "highpass"]
+    pub highpass_cutoff: FloatParam,
+}
+
+impl StageParams {
+    pub fn new(stage_number: usize, oversampling_times: Arc<AtomicF32>) -> Self {
+        Self {
+            style: EnumParam::new(
+                format!("Stage {stage_number} Clip Style"),
+                ClipStyle::HardClip,
+            ),
+
+            order: EnumParam::new(
+                format!("Stage {stage_number} ADAA Order"),
+                AntiderivativeOrder::FirstOrder,
+            ),
+
+            drive: FloatParam::new(
+                format!("Stage {stage_number} Drive"),
+                util::db_to_gain(0.0),
+                FloatRange::Skewed {
+                    min: util::db_to_gain(0.0),
+                    max: util::db_to_gain(36.0),
+                    factor: FloatRange::gain_skew_factor(0.0, 36.0),
+                },
+            )
+            .with_smoother(SmoothingStyle::OversamplingAware(
+                oversampling_times.clone(),
+                &SmoothingStyle::Logarithmic(50.0),
+            ))
+            .with_unit(" dB")
+            .with_value_to_string(formatters::v2s_f32_gain_to_db(2))
+            .with_string_to_value(formatters::s2v_f32_gain_to_db()),
+
+            lowpass_cutoff: FloatParam::new(
+                format!("Stage {stage_number} Lowpass"),
+                STAGE_LOWPASS_DEFAULT,
+                FloatRange::Skewed {
+                    min: 200.0,
+                    max: 20000.0,
+                    factor: FloatRange::skew_factor(-1.0),
+                },
+            )
+            .with_smoother(SmoothingStyle::OversamplingAware(
+                oversampling_times.clone(),
+                &SmoothingStyle::Logarithmic(10.0),
+            ))
+            .with_value_to_string(formatters::v2s_f32_hz_then_khz(0))
+            .with_string_to_value(formatters::s2v_f32_hz_then_khz()),
+
+            highpass_cutoff: FloatParam::new(
+                format!("Stage {stage_number} Highpass"),
+                STAGE_HIGHPASS_DEFAULT,
+                FloatRange::Skewed {
+                    min: 10.0,
+                    max: 2000.0,
+                    factor: FloatRange::skew_factor(-1.0),
+                },
+            )
+            .with_smoother(SmoothingStyle::OversamplingAware(
+                oversampling_times,
+                &SmoothingStyle::Logarithmic(10.0),
+            ))
+            .with_value_to_string(formatters::v2s_f32_hz_then_khz(0))
+            .with_string_to_value(formatters::s2v_f32_hz_then_khz()),
+        }
+    }
+}
+
+/// One channel of an additional stage, running at the oversampled rate.
+pub struct SaturationStage {
+    processor: CrossfadingProcessor,
+    lowpass: OnePole,
+    highpass: OnePole,
+    lowpass_cutoff: f32,
+    highpass_cutoff: f32,
+    sample_rate: f32,
+}
+
+impl Default for SaturationStage {
+    fn default() -> Self {
+        Self {
+            processor: CrossfadingProcessor::default(),
+            lowpass: OnePole::default(),
+            highpass: OnePole::default(),
+            lowpass_cutoff: STAGE_LOWPASS_DEFAULT,
+            highpass_cutoff: STAGE_HIGHPASS_DEFAULT,
+            sample_rate: 44100.0,
+        }
+    }
+}
+
+impl SaturationStage {
+    /// Sets the oversampled rate the inter-stage filters run at.
+    pub fn set_sample_rate(&mut self, sample_rate: f32) {
+        self.sample_rate = sample_rate;
+        self.lowpass.set_cutoff(self.lowpass_cutoff, sample_rate);
+        self.highpass.set_cutoff(self.highpass_cutoff, sample_rate);
+        self.processor.set_sample_rate(sample_rate);
+    }
+
+    pub fn set_cutoffs(&mut self, lowpass_cutoff: f32, highpass_cutoff: f32) {
+        if lowpass_cutoff != self.lowpass_cutoff {
+            self.lowpass_cutoff = lowpass_cutoff;
+            self.lowpass.set_cutoff(lowpass_cutoff, self.sample_rate);
+        }
+        if highpass_cutoff != self.highpass_cutoff {
+            self.highpass_cutoff = highpass_cutoff;
+            self.highpass.set_cutoff(highpass_cutoff, self.sample_rate);
+        }
+    }
+
+    pub fn set_state(&mut self, style: ClipStyle, order: AntiderivativeOrder) {
+        self.processor.set_state(style, order);
+    }
+
+    pub fn set_settings(&mut self, settings: &StyleSettings) {
+        self.processor.set_settings(settings);
+    }
+
+    #[inline]
+    pub fn process(&mut self, x: f32, drive: f32) -> f32 {
+        let filtered = self
+            .lowpass
+            .process_lowpass(self.highpass.process_highpass(x));
+        self.processor.process(filtered * drive)
+    }
+
+    pub fn reset(&mut self, style: ClipStyle, order: AntiderivativeOrder) {
+        self.processor.reset(style, order);
+        self.lowpass.reset();
+        self.highpass.reset();
+    }
+}
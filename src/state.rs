@@ -0,0 +1,88 @@
+//! Versioning of saved state. Sessions store [`STATE_VERSION`] alongside the parameters, and
+//! [`migrate`] brings state saved by an older version up to date before nih-plug restores it, so
+//! renamed parameter ids and reordered enums keep old projects sounding the same.
+
+use nih_plug::prelude::nih_log;
+use nih_plug::wrapper::state::{ParamValue, PluginState};
+
+/// Version of the state this build saves. Raise it, and add a step to [`migrate`], whenever a
+/// parameter id changes or an enum's variants move.
+pub const STATE_VERSION: u32 = 1;
+/// Key of the persisted field holding the version.
+const STATE_VERSION_KEY: &str = "state-version";
+
+/// Parameters holding a clip style.
+const CLIP_STYLE_PARAMS: [&str; 5] = [
+    "nl proc",
+    "nl proc b",
+    "stage 2_style",
+    "stage 3_style",
+    "stage 4_style",
+];
+/// Clip style ids in the order of jdsp's `ProcessorStyle`, which the styles were before they had
+/// ids of their own and which sessions from then store as an index.
+const INDEXED_CLIP_STYLES: [&str; 2] = ["hard clip", "tanh"];
+
+/// Renamed parameter ids as `(version, old id, new id)`, where `version` is the first to use the
+/// new id. Applied in order, so an id can be renamed more than once.
+const RENAMED_IDS: &[(u32, &str, &str)] = &[];
+
+/// The version `state` was saved with. State from before versioning counts as version 0.
+fn saved_version(state: &PluginState) -> u32 {
+    state
+        .fields
+        .get(STATE_VERSION_KEY)
+        .and_then(|version| version.parse().ok())
+        .unwrap_or(0)
+}
+
+/// Updates `state` from whichever version saved it to [`STATE_VERSION`].
+pub fn migrate(state: &mut PluginState) {
+    let version = saved_version(state);
+    if version > STATE_VERSION {
+        nih_log!(
+            "State was saved by a newer version of the plugin (state version {version}), some \
+             settings may not load"
+        );
+    }
+
+    if version < 1 {
+        clip_style_indices_to_ids(state);
+    }
+
+    for (renamed_in, old_id, new_id) in RENAMED_IDS {
+        if version < *renamed_in {
+            if let Some(value) = state.params.remove(*old_id) {
+                state.params.insert(new_id.to_string(), value);
+            }
+        }
+    }
+
+    state
+        .fields
+        .insert(STATE_VERSION_KEY.to_string(), STATE_VERSION.to_string());
+}
+
+/// Replaces clip styles stored as an index with their ids. The index may come back as either
+/// number type.
+fn clip_style_indices_to_ids(state: &mut PluginState) {
+    for id in CLIP_STYLE_PARAMS {
+        let index = match state.params.get(id) {
+            Some(ParamValue::I32(index)) => *index as usize,
+            Some(ParamValue::F32(index)) => *index as usize,
+            _ => continue,
+        };
+
+        match INDEXED_CLIP_STYLES.get(index) {
+            Some(style) => {
+                state
+                    .params
+                    .insert(id.to_string(), ParamValue::String(style.to_string()));
+            }
+            None => {
+                nih_log!("Unknown clip style index {index} for '{id}', using the default");
+                state.params.remove(id);
+            }
+        }
+    }
+}
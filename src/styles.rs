@@ -0,0 +1,124 @@
+use jdsp::{AntiderivativeOrder, NonlinearProcessor, ProcessorState::State, ProcessorStyle};
+use nih_plug::prelude::*;
+
+use crate::chebyshev::{ChebyshevProcessor, ChebyshevShape};
+use crate::diode::{DiodeClipper, DiodePreset};
+use crate::hysteresis::{Hysteresis, HysteresisSettings};
+
+/// Every curve the plugin offers. The memoryless curves come from jdsp's anti-derivative
+/// processors and keep the indices they had as `ProcessorStyle`s.
+#[derive(Enum, Debug, PartialEq, Eq, Clone, Copy)]
+pub enum ClipStyle {
+    #[id = "hard clip"]
+    #[name = "Hard Clip"]
+    HardClip,
+    #[id = "tanh"]
+    #[name = "Tanh"]
+    Tanh,
+    #[id = "hysteresis"]
+    #[name = "Hysteresis"]
+    Hysteresis,
+    #[id = "diode clipper"]
+    #[name = "Diode Clipper"]
+    DiodeClipper,
+    #[id = "chebyshev"]
+    #[name = "Chebyshev Harmonics"]
+    Chebyshev,
+}
+
+impl ClipStyle {
+    /// The anti-derivative processor style, if this is one of the memoryless curves.
+    fn processor_style(self) -> Option<ProcessorStyle> {
+        match self {
+            ClipStyle::HardClip => Some(ProcessorStyle::HardClip),
+            ClipStyle::Tanh => Some(ProcessorStyle::Tanh),
+            ClipStyle::Hysteresis | ClipStyle::DiodeClipper | ClipStyle::Chebyshev => None,
+        }
+    }
+}
+
+// inputs the second order anti-derivative processors look back over
+const ADAA_HISTORY: usize = 2;
+
+/// Settings of the styles that have their own controls, shared by every processor using them.
+#[derive(Clone, Copy, Debug)]
+pub struct StyleSettings {
+    pub hysteresis: HysteresisSettings,
+    pub diode_preset: DiodePreset,
+    pub chebyshev: ChebyshevShape,
+}
+
+impl Default for StyleSettings {
+    fn default() -> Self {
+        Self {
+            hysteresis: HysteresisSettings::default(),
+            diode_preset: DiodePreset::Silicon,
+            chebyshev: ChebyshevShape::default(),
+        }
+    }
+}
+
+/// Runs whichever curve is selected. Stateful styles ignore the ADAA order and rely on the
+/// oversampling alone to keep aliasing down, the Chebyshev shaper runs its own ADAA.
+pub struct StyleProcessor {
+    style: ClipStyle,
+    adaa: NonlinearProcessor,
+    hysteresis: Hysteresis,
+    diode: DiodeClipper,
+    chebyshev: ChebyshevProcessor,
+}
+
+impl Default for StyleProcessor {
+    fn default() -> Self {
+        Self {
+            style: ClipStyle::HardClip,
+            adaa: NonlinearProcessor::new(),
+            hysteresis: Hysteresis::default(),
+            diode: DiodeClipper::default(),
+            chebyshev: ChebyshevProcessor::default(),
+        }
+    }
+}
+
+impl StyleProcessor {
+    pub fn set_sample_rate(&mut self, sample_rate: f32) {
+        self.hysteresis.set_sample_rate(sample_rate);
+        self.diode.set_sample_rate(sample_rate);
+    }
+
+    pub fn set_settings(&mut self, settings: &StyleSettings) {
+        self.hysteresis.set_settings(settings.hysteresis);
+        self.diode.set_preset(settings.diode_preset);
+        self.chebyshev.set_shape(settings.chebyshev);
+    }
+
+    pub fn set_state(&mut self, style: ClipStyle, order: AntiderivativeOrder) {
+        self.style = style;
+        self.chebyshev.set_order(order);
+        if let Some(processor_style) = style.processor_style() {
+            self.adaa
+                .compare_and_change_state(State(processor_style, order));
+        }
+    }
+
+    #[inline]
+    pub fn process(&mut self, x: f32) -> f32 {
+        match self.style {
+            ClipStyle::HardClip | ClipStyle::Tanh => self.adaa.process(x),
+            ClipStyle::Hysteresis => self.hysteresis.process(x),
+            ClipStyle::DiodeClipper => self.diode.process(x),
+            ClipStyle::Chebyshev => self.chebyshev.process(x),
+        }
+    }
+
+    /// Clears the history of every style in place, so it can run on the audio thread.
+    pub fn reset(&mut self) {
+        // the anti-derivative processors only remember their last inputs, silence clears them
+        (0..ADAA_HISTORY).for_each(|_| {
+            self.adaa.process(0.0);
+        });
+        self.hysteresis.reset();
+        self.diode.reset();
+        self.chebyshev.reset();
+    }
+}
@@ -0,0 +1,308 @@
+//! JSON test vectors for the oversampled nonlinear chain.
+//!
+//! Each vector stores an input block, the expected output and the chain settings it was rendered
+//! with. Older vectors only describe their settings in the free-form `name`, so missing fields
+//! fall back to parsing it (e.g. `"hc 4x adaa level 2 x10 gain size 64"`).
+//!
+//! [`summarize_results`] reports how a rendered block differs from the expected output, both for
+//! the tests and for the generator when it re-renders vectors.
+
+use std::error::Error;
+use std::fmt;
+use std::fs::{self, File};
+use std::io::{BufReader, BufWriter};
+use std::path::{Path, PathBuf};
+
+use jdsp::{AntiderivativeOrder, OversampleFactor, ProcessorStyle};
+use nih_plug::prelude::Enum;
+use serde::{Deserialize, Serialize};
+
+use crate::measure::{process_chain_in_blocks, ChainConfig};
+
+pub const TEST_VECTOR_DIR: &str = "./tests/json_test_data";
+
+/// Largest difference from the expected output that still counts as a match.
+pub const ERR_TOL: f32 = 1e-5;
+
+/// Comparison of a rendered block against a vector's expected output.
+pub struct TotalResult {
+    pub incorrect_results: Vec<Option<ADAAResult>>,
+    pub num_incorrect_results: Option<usize>,
+    pub num_total_tests: usize,
+    pub perc_correct_results: Option<f32>,
+    pub avg_difference: Option<f32>,
+}
+
+impl TotalResult {
+    pub fn new(results: Vec<Option<ADAAResult>>) -> Self {
+        TotalResult {
+            incorrect_results: results
+                .clone()
+                .into_iter()
+                .filter(|v| v.is_some())
+                .collect(),
+            num_incorrect_results: None,
+            num_total_tests: results.len(),
+            perc_correct_results: None,
+            avg_difference: None,
+        }
+    }
+
+    pub fn initialize(&mut self) {
+        self.num_incorrect_results = if !self.incorrect_results.is_empty() {
+            self.avg_difference = Some(
+                self.incorrect_results
+                    .clone()
+                    .into_iter()
+                    .fold(0.0, |acc, v| acc + v.unwrap().difference)
+                    / self.incorrect_results.len() as f32,
+            );
+            Some(self.incorrect_results.len())
+        } else {
+            None
+        };
+        self.perc_correct_results = Some(
+            1. - (self.num_incorrect_results.unwrap_or(0) as f32 / self.num_total_tests as f32),
+        );
+    }
+}
+
+impl fmt::Debug for TotalResult {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "TotalResult: \n\tnum_incorrect: {}\n\tperc_correct: {}%\n\tavg difference: {}\n\tmax difference: {}\n\tincorrect: {:?}",
+            self.num_incorrect_results.unwrap_or(0),
+            self.perc_correct_results.unwrap_or(100.0),
+            self.avg_difference.unwrap_or(0.0),
+            self.incorrect_results.clone().into_iter().fold(0.0, |acc, v| v.unwrap().difference.max(acc)),
+            self.incorrect_results
+                .clone()
+                .into_iter()
+                .map(|v| format!("{:?}",
+                    v.unwrap()))
+        )
+    }
+}
+
+/// A single output sample that differs from the expected one by more than [`ERR_TOL`].
+#[derive(Clone)]
+pub struct ADAAResult {
+    pub my_result: f32,
+    pub expected_result: f32,
+    pub difference: f32,
+}
+
+impl fmt::Debug for ADAAResult {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "My result: {}, expected_result: {}, difference: {}",
+            self.my_result, self.expected_result, self.difference
+        )
+    }
+}
+
+/// Compares `result` sample by sample against `expected_result`.
+pub fn summarize_results(result: &[f32], expected_result: &[f32]) -> TotalResult {
+    let results: Vec<Option<ADAAResult>> = result
+        .iter()
+        .zip(expected_result.iter())
+        .map(|(r, e)| {
+            let res = (r - e).abs() < ERR_TOL;
+            if res {
+                None
+            } else {
+                Some(ADAAResult {
+                    my_result: *r,
+                    expected_result: *e,
+                    difference: (r - e).abs(),
+                })
+            }
+        })
+        .collect();
+
+    let mut total = TotalResult::new(results);
+    total.initialize();
+    total
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct TestVector {
+    pub name: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub style: Option<String>,
+    /// Antiderivative order, `1` or `2`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub order: Option<u32>,
+    /// Oversampling ratio, `2`, `4`, `8` or `16`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub factor: Option<u32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub drive: Option<f32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub block_size: Option<usize>,
+    pub input: Vec<f32>,
+    pub expected_output: Vec<f32>,
+}
+
+#[derive(Clone, Copy)]
+pub struct VectorConfig {
+    pub chain: ChainConfig,
+    pub block_size: usize,
+}
+
+impl TestVector {
+    pub fn new(name: String, config: VectorConfig, input: Vec<f32>) -> Self {
+        let mut vector = TestVector {
+            name,
+            style: Some(style_name(config.chain.style).to_string()),
+            order: Some(order_number(config.chain.order)),
+            factor: Some(factor_times(config.chain.factor)),
+            drive: Some(config.chain.drive),
+            block_size: Some(config.block_size),
+            input,
+            expected_output: Vec::new(),
+        };
+        vector.expected_output = vector.render(&config);
+        vector
+    }
+
+    pub fn read<P: AsRef<Path>>(path: P) -> Result<Self, Box<dyn Error>> {
+        let reader = BufReader::new(File::open(path)?);
+        Ok(serde_json::from_reader(reader)?)
+    }
+
+    pub fn write<P: AsRef<Path>>(&self, path: P) -> Result<(), Box<dyn Error>> {
+        let writer = BufWriter::new(File::create(path)?);
+        Ok(serde_json::to_writer(writer, self)?)
+    }
+
+    /// Resolves the chain settings, preferring explicit fields over the ones parsed from `name`.
+    pub fn config(&self) -> Result<VectorConfig, String> {
+        let name = self.name.to_lowercase();
+        let tokens: Vec<&str> = name.split_whitespace().collect();
+        let number_after = |word: &str| {
+            tokens
+                .windows(2)
+                .find(|pair| pair[0] == word)
+                .and_then(|pair| pair[1].parse::<u32>().ok())
+        };
+
+        let style = match &self.style {
+            Some(style) => parse_style(style),
+            None => parse_style(&name),
+        }
+        .ok_or_else(|| format!("no processor style in '{}'", self.name))?;
+
+        let order = self
+            .order
+            .or_else(|| number_after("level"))
+            .and_then(order_from_number)
+            .ok_or_else(|| format!("no antiderivative order in '{}'", self.name))?;
+
+        let factor = self
+            .factor
+            .or_else(|| {
+                tokens
+                    .iter()
+                    .find_map(|t| t.strip_suffix('x').and_then(|n| n.parse().ok()))
+            })
+            .and_then(factor_from_times)
+            .ok_or_else(|| format!("no oversample factor in '{}'", self.name))?;
+
+        let drive = self
+            .drive
+            .or_else(|| {
+                tokens
+                    .iter()
+                    .find_map(|t| t.strip_prefix('x').and_then(|n| n.parse().ok()))
+            })
+            .ok_or_else(|| format!("no drive in '{}'", self.name))?;
+
+        let block_size = self
+            .block_size
+            .or_else(|| number_after("size").map(|n| n as usize))
+            .unwrap_or(self.input.len());
+
+        Ok(VectorConfig {
+            chain: ChainConfig {
+                style,
+                order,
+                factor,
+                drive,
+            },
+            block_size,
+        })
+    }
+
+    /// Renders `input` through the chain with the current DSP.
+    pub fn render(&self, config: &VectorConfig) -> Vec<f32> {
+        process_chain_in_blocks(&config.chain, &self.input, config.block_size)
+    }
+}
+
+/// Every `.json` file in `dir`, sorted by path.
+pub fn discover<P: AsRef<Path>>(dir: P) -> std::io::Result<Vec<PathBuf>> {
+    let mut paths: Vec<PathBuf> = fs::read_dir(dir)?
+        .filter_map(|entry| entry.ok().map(|e| e.path()))
+        .filter(|path| path.extension().is_some_and(|ext| ext == "json"))
+        .collect();
+    paths.sort();
+
+    Ok(paths)
+}
+
+fn normalize(s: &str) -> String {
+    s.chars()
+        .filter(|c| c.is_ascii_alphanumeric())
+        .map(|c| c.to_ascii_lowercase())
+        .collect()
+}
+
+/// Matches a style against the parameter display names, ignoring case and spacing. `hc` is
+/// accepted as short for hard clip, as in the original vector names.
+pub fn parse_style(s: &str) -> Option<ProcessorStyle> {
+    let s = normalize(s);
+    if s.starts_with("hc") {
+        return Some(ProcessorStyle::HardClip);
+    }
+
+    ProcessorStyle::variants()
+        .iter()
+        .position(|variant| s.contains(&normalize(variant)))
+        .map(ProcessorStyle::from_index)
+}
+
+pub fn style_name(style: ProcessorStyle) -> &'static str {
+    ProcessorStyle::variants()[style.to_index()]
+}
+
+pub fn order_number(order: AntiderivativeOrder) -> u32 {
+    match order {
+        AntiderivativeOrder::FirstOrder => 1,
+        AntiderivativeOrder::SecondOrder => 2,
+    }
+}
+
+pub fn order_from_number(n: u32) -> Option<AntiderivativeOrder> {
+    match n {
+        1 => Some(AntiderivativeOrder::FirstOrder),
+        2 => Some(AntiderivativeOrder::SecondOrder),
+        _ => None,
+    }
+}
+
+pub fn factor_times(factor: OversampleFactor) -> u32 {
+    2_u32.pow(factor as u32)
+}
+
+pub fn factor_from_times(times: u32) -> Option<OversampleFactor> {
+    match times {
+        2 => Some(OversampleFactor::TwoTimes),
+        4 => Some(OversampleFactor::FourTimes),
+        8 => Some(OversampleFactor::EightTimes),
+        16 => Some(OversampleFactor::SixteenTimes),
+        _ => None,
+    }
+}
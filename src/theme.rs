@@ -0,0 +1,71 @@
+//! Editor color themes. Both built-in themes live in `assets/theme.css`, scoped to a `.dark` or
+//! `.light` class on the editor's root view, and a user stylesheet from the config directory is
+//! layered on top of them.
+
+use nih_plug_vizia::vizia::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use std::sync::Weak;
+use std::time::{Duration, SystemTime};
+
+use crate::editor;
+
+pub const THEME_CSS: &str = include_str!("../assets/theme.css");
+
+const USER_STYLESHEET: &str = "theme.css";
+const WATCH_INTERVAL: Duration = Duration::from_millis(500);
+
+#[derive(Serialize, Deserialize, Debug, Default, PartialEq, Eq, Clone, Copy)]
+pub enum EditorTheme {
+    #[default]
+    Dark,
+    Light,
+}
+
+impl EditorTheme {
+    pub const ALL: [EditorTheme; 2] = [EditorTheme::Dark, EditorTheme::Light];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            EditorTheme::Dark => "Dark",
+            EditorTheme::Light => "Light",
+        }
+    }
+
+    /// The class on the editor's root view that selects this theme's rules.
+    pub fn class(self) -> &'static str {
+        match self {
+            EditorTheme::Dark => "dark",
+            EditorTheme::Light => "light",
+        }
+    }
+}
+
+/// `<config dir>/Nonlinear_ADAA/theme.css`, e.g. `~/.config/Nonlinear_ADAA/theme.css` on Linux.
+pub fn user_stylesheet_path() -> Option<PathBuf> {
+    dirs::config_dir().map(|dir| dir.join("Nonlinear_ADAA").join(USER_STYLESHEET))
+}
+
+fn modified(path: &Path) -> Option<SystemTime> {
+    std::fs::metadata(path)
+        .and_then(|metadata| metadata.modified())
+        .ok()
+}
+
+/// Polls the user stylesheet and emits `event()` whenever it changes, including when it is created
+/// after the editor opened.
+pub fn watch_user_stylesheet<E, F>(cx: &mut Context, path: PathBuf, alive: Weak<()>, event: F)
+where
+    E: Send + 'static,
+    F: Fn() -> E + Send + 'static,
+{
+    let mut last_modified = modified(&path);
+    editor::poll_while_open(cx, alive, WATCH_INTERVAL, move || {
+        let current = modified(&path);
+        if current == last_modified {
+            return None;
+        }
+        last_modified = current;
+        Some(event())
+    });
+}
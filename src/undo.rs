@@ -0,0 +1,97 @@
+//! Undo history for parameter edits made in the editor. Hosts differ in whether they record
+//! plugin parameter changes, so the editor keeps its own. Each gesture, from the begin to the end
+//! of setting a parameter, is one step, and so is each randomization.
+
+use nih_plug::prelude::ParamPtr;
+
+/// Steps kept before the oldest is dropped.
+pub const MAX_STEPS: usize = 100;
+
+/// One gesture on one parameter, as normalized values.
+#[derive(Clone, Copy, Debug)]
+pub struct ParamChange {
+    pub param: ParamPtr,
+    pub before: f32,
+    pub after: f32,
+}
+
+#[derive(Default)]
+pub struct UndoHistory {
+    undo: Vec<Vec<ParamChange>>,
+    redo: Vec<Vec<ParamChange>>,
+    // gestures that have begun but not ended
+    pending: Vec<ParamChange>,
+}
+
+impl UndoHistory {
+    pub fn begin(&mut self, param: ParamPtr, value: f32) {
+        self.pending.retain(|change| change.param != param);
+        self.pending.push(ParamChange {
+            param,
+            before: value,
+            after: value,
+        });
+    }
+
+    pub fn set(&mut self, param: ParamPtr, value: f32) {
+        if let Some(change) = self.pending.iter_mut().find(|change| change.param == param) {
+            change.after = value;
+        }
+    }
+
+    /// Ends the gesture and records it, unless it left the parameter where it was.
+    pub fn end(&mut self, param: ParamPtr) {
+        let Some(idx) = self.pending.iter().position(|change| change.param == param) else {
+            return;
+        };
+
+        let change = self.pending.remove(idx);
+        self.record(vec![change]);
+    }
+
+    /// Records changes made together as one step. Changes that left their parameter where it was
+    /// are dropped.
+    pub fn record(&mut self, mut changes: Vec<ParamChange>) {
+        changes.retain(|change| change.before != change.after);
+        if changes.is_empty() {
+            return;
+        }
+
+        if self.undo.len() == MAX_STEPS {
+            self.undo.remove(0);
+        }
+        self.undo.push(changes);
+        self.redo.clear();
+    }
+
+    pub fn can_undo(&self) -> bool {
+        !self.undo.is_empty()
+    }
+
+    pub fn can_redo(&self) -> bool {
+        !self.redo.is_empty()
+    }
+
+    /// The parameters and the normalized values to restore them to.
+    pub fn undo(&mut self) -> Option<Vec<(ParamPtr, f32)>> {
+        let changes = self.undo.pop()?;
+        let step = changes
+            .iter()
+            .rev()
+            .map(|change| (change.param, change.before))
+            .collect();
+        self.redo.push(changes);
+        Some(step)
+    }
+
+    /// The parameters and the normalized values to set them to again.
+    pub fn redo(&mut self) -> Option<Vec<(ParamPtr, f32)>> {
+        let changes = self.redo.pop()?;
+        let step = changes
+            .iter()
+            .map(|change| (change.param, change.after))
+            .collect();
+        self.undo.push(changes);
+        Some(step)
+    }
+}
@@ -0,0 +1,138 @@
+use std::collections::BTreeMap;
+use std::fs;
+
+use Nonlinear_ADAA::measure::{harmonic_analysis, measure_sweep, measure_tone, ChainConfig};
+use Nonlinear_ADAA::measure::{FFT_SIZE, MEASURE_SAMPLE_RATE, TEST_TONES};
+
+const DRIVE: f32 = 10.0;
+// worst signal-to-alias ratio of each configuration, recorded with
+// `ALIAS_BLESS=1 cargo test --release --features measure --test aliasing` and committed
+const THRESHOLD_FILE: &str = "./tests/alias_thresholds.json";
+// how far (in dB) a configuration may drop below its stored signal-to-alias ratio
+const REGRESSION_TOLERANCE_DB: f64 = 1.0;
+// set to rewrite the stored thresholds from the current measurements
+const BLESS_VAR: &str = "ALIAS_BLESS";
+
+fn read_thresholds() -> BTreeMap<String, f64> {
+    let contents = fs::read_to_string(THRESHOLD_FILE).unwrap_or_else(|e| {
+        panic!("Failed to read {THRESHOLD_FILE} ({e}), run with {BLESS_VAR}=1 to record it")
+    });
+    serde_json::from_str(&contents).expect("Malformed alias threshold file")
+}
+
+#[test]
+fn test_alias_regression() {
+    let blessing = std::env::var_os(BLESS_VAR).is_some();
+    let thresholds = if blessing {
+        BTreeMap::new()
+    } else {
+        read_thresholds()
+    };
+    let mut measured = BTreeMap::new();
+    let mut regressions = Vec::new();
+
+    println!(
+        "{:<40} {:>10} {:>10} {:>10} {:>10} {:>10} {:>10}",
+        "config", "2k", "5k", "10k", "15k", "sweep", "stored"
+    );
+
+    for (name, config) in ChainConfig::all(DRIVE) {
+        let tones: Vec<f64> = TEST_TONES
+            .iter()
+            .map(|freq| measure_tone(&config, *freq).signal_to_alias_db())
+            .collect();
+        let sweep = measure_sweep(&config).signal_to_alias_db();
+        let worst = tones.iter().copied().fold(sweep, f64::min);
+
+        let stored = thresholds.get(&name).copied();
+
+        println!(
+            "{:<40} {:>10.2} {:>10.2} {:>10.2} {:>10.2} {:>10.2} {:>10}",
+            name,
+            tones[0],
+            tones[1],
+            tones[2],
+            tones[3],
+            sweep,
+            stored.map_or("-".to_string(), |s| format!("{s:.2}"))
+        );
+
+        if let Some(stored) = stored {
+            if worst < stored - REGRESSION_TOLERANCE_DB {
+                regressions.push(format!("{name}: {worst:.2} dB, stored {stored:.2} dB"));
+            }
+        }
+
+        measured.insert(name, worst);
+    }
+
+    if blessing {
+        fs::write(
+            THRESHOLD_FILE,
+            serde_json::to_string_pretty(&measured).expect("Failed to serialize thresholds"),
+        )
+        .expect("Failed to write alias threshold file");
+        return;
+    }
+
+    let missing: Vec<&String> = measured
+        .keys()
+        .filter(|k| !thresholds.contains_key(*k))
+        .collect();
+    assert!(
+        missing.is_empty(),
+        "no stored threshold for {missing:?}, run with {BLESS_VAR}=1 to record them"
+    );
+
+    assert!(
+        regressions.is_empty(),
+        "signal-to-alias ratio regressed:\n\t{}",
+        regressions.join("\n\t")
+    );
+}
+
+/// A tone between bins with known 2nd and 3rd harmonics.
+fn tone_with_harmonics(freq: f32, h2: f32, h3: f32) -> Vec<f32> {
+    (0..FFT_SIZE)
+        .map(|n| {
+            let phase = 2.0 * std::f32::consts::PI * freq * n as f32 / MEASURE_SAMPLE_RATE;
+            phase.sin() + h2 * (2.0 * phase).sin() + h3 * (3.0 * phase).sin()
+        })
+        .collect()
+}
+
+#[test]
+fn test_harmonic_analysis_levels() {
+    let signal = tone_with_harmonics(997.3, 0.01, 0.1);
+
+    for fundamental_hz in [Some(1000.0), None] {
+        let analysis = harmonic_analysis(&signal, MEASURE_SAMPLE_RATE, fundamental_hz)
+            .expect("tone not found");
+
+        assert!((analysis.fundamental_hz - 997.3).abs() < 0.5);
+        assert!((analysis.harmonics_db[0].unwrap() + 40.0).abs() < 0.1);
+        assert!((analysis.harmonics_db[1].unwrap() + 20.0).abs() < 0.1);
+        assert!(analysis.harmonics_db[2..]
+            .iter()
+            .all(|level| level.unwrap() < -100.0));
+        // -20 dB and -40 dB add up to -19.96 dB
+        assert!((analysis.thd_db + 19.96).abs() < 0.1);
+        assert!((analysis.thd_n_db - analysis.thd_db).abs() < 0.1);
+        assert!(analysis.alias_db < -80.0);
+    }
+}
+
+#[test]
+fn test_harmonic_analysis_skips_harmonics_above_nyquist() {
+    let signal = tone_with_harmonics(5000.0, 0.0, 0.1);
+    let analysis = harmonic_analysis(&signal, MEASURE_SAMPLE_RATE, Some(5000.0)).unwrap();
+
+    // 4 x 5 kHz is the last one below 22.05 kHz
+    assert!(analysis.harmonics_db[..3].iter().all(Option::is_some));
+    assert!(analysis.harmonics_db[3..].iter().all(Option::is_none));
+}
+
+#[test]
+fn test_harmonic_analysis_ignores_silence() {
+    assert!(harmonic_analysis(&vec![0.0; FFT_SIZE], MEASURE_SAMPLE_RATE, None).is_none());
+}
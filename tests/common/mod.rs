@@ -0,0 +1,207 @@
+//! A minimal fake host for driving `NonlinearAdaa` through the `Plugin` trait.
+
+#![allow(dead_code)]
+
+use std::cell::Cell;
+use std::collections::VecDeque;
+
+use nih_plug::prelude::*;
+use Nonlinear_ADAA::NonlinearAdaa;
+
+pub const SAMPLE_RATE: f32 = 44100.0;
+pub const MAX_BUFFER_SIZE: u32 = 512;
+
+/// Records what the plugin reports back to the host and hands it queued MIDI events, with a
+/// stopped transport.
+pub struct MockContext {
+    pub latency: Cell<u32>,
+    /// Events for the next `process` call, in timing order.
+    pub events: VecDeque<PluginNoteEvent<NonlinearAdaa>>,
+    pub transport: Transport,
+}
+
+impl Default for MockContext {
+    fn default() -> Self {
+        Self {
+            latency: Cell::new(0),
+            events: VecDeque::new(),
+            transport: Transport::new(SAMPLE_RATE),
+        }
+    }
+}
+
+impl InitContext<NonlinearAdaa> for MockContext {
+    fn plugin_api(&self) -> PluginApi {
+        PluginApi::Standalone
+    }
+
+    fn execute(&self, _task: <NonlinearAdaa as Plugin>::BackgroundTask) {}
+
+    fn set_latency_samples(&self, samples: u32) {
+        self.latency.set(samples);
+    }
+
+    fn set_current_voice_capacity(&self, _capacity: u32) {}
+}
+
+impl ProcessContext<NonlinearAdaa> for MockContext {
+    fn plugin_api(&self) -> PluginApi {
+        PluginApi::Standalone
+    }
+
+    fn execute_background(&self, _task: <NonlinearAdaa as Plugin>::BackgroundTask) {}
+
+    fn execute_gui(&self, _task: <NonlinearAdaa as Plugin>::BackgroundTask) {}
+
+    fn transport(&self) -> &Transport {
+        &self.transport
+    }
+
+    fn next_event(&mut self) -> Option<PluginNoteEvent<NonlinearAdaa>> {
+        self.events.pop_front()
+    }
+
+    fn send_event(&mut self, _event: PluginNoteEvent<NonlinearAdaa>) {}
+
+    fn set_latency_samples(&self, samples: u32) {
+        self.latency.set(samples);
+    }
+
+    fn set_current_voice_capacity(&self, _capacity: u32) {}
+}
+
+pub struct TestHost {
+    pub plugin: NonlinearAdaa,
+    pub context: MockContext,
+}
+
+impl TestHost {
+    /// Creates, initializes and resets the plugin the way a host would before playback.
+    pub fn new() -> Self {
+        let mut host = TestHost {
+            plugin: NonlinearAdaa::default(),
+            context: MockContext::default(),
+        };
+        host.initialize();
+        host
+    }
+
+    pub fn initialize(&mut self) {
+        assert!(self.plugin.initialize(
+            &NonlinearAdaa::AUDIO_IO_LAYOUTS[0],
+            &BufferConfig {
+                sample_rate: SAMPLE_RATE,
+                min_buffer_size: None,
+                max_buffer_size: MAX_BUFFER_SIZE,
+                process_mode: ProcessMode::Realtime,
+            },
+            &mut self.context,
+        ));
+
+        for (_, param, _) in self.plugin.params().param_map() {
+            unsafe { param.update_smoother(SAMPLE_RATE, true) };
+        }
+
+        self.plugin.reset();
+    }
+
+    /// Sets a parameter by its ID and plain value, as host automation would between two
+    /// `process` calls. Smoothing is kept unless `snap` is set.
+    pub fn set_param(&self, id: &str, plain: f32, snap: bool) {
+        let (_, param, _) = self
+            .plugin
+            .params()
+            .param_map()
+            .into_iter()
+            .find(|(param_id, _, _)| param_id == id)
+            .unwrap_or_else(|| panic!("unknown parameter id '{id}'"));
+
+        unsafe {
+            param.set_normalized_value(param.preview_normalized(plain));
+            param.update_smoother(SAMPLE_RATE, snap);
+        }
+    }
+
+    /// Runs a single `process` call over the two channels in place.
+    pub fn process(&mut self, left: &mut [f32], right: &mut [f32]) -> ProcessStatus {
+        assert_eq!(left.len(), right.len());
+        assert!(left.len() <= MAX_BUFFER_SIZE as usize);
+
+        let num_samples = left.len();
+        let mut buffer = Buffer::default();
+        unsafe {
+            buffer.set_slices(num_samples, |slices| {
+                slices.clear();
+                slices.push(left);
+                slices.push(right);
+            });
+        }
+        let mut aux = AuxiliaryBuffers {
+            inputs: &mut [],
+            outputs: &mut [],
+        };
+
+        self.plugin
+            .process(&mut buffer, &mut aux, &mut self.context)
+    }
+
+    /// Runs a single `process` call split at `offset` the way a host with sample accurate
+    /// automation delivers a parameter change in the middle of a buffer.
+    pub fn process_with_automation(
+        &mut self,
+        left: &mut [f32],
+        right: &mut [f32],
+        offset: usize,
+        id: &str,
+        plain: f32,
+    ) {
+        let (left_before, left_after) = left.split_at_mut(offset);
+        let (right_before, right_after) = right.split_at_mut(offset);
+
+        self.process(left_before, right_before);
+        self.set_param(id, plain, false);
+        self.process(left_after, right_after);
+    }
+
+    /// Processes a mono signal on both channels in host sized buffers and returns the left
+    /// channel.
+    pub fn process_signal(&mut self, input: &[f32], buffer_size: usize) -> Vec<f32> {
+        let mut output = Vec::with_capacity(input.len());
+
+        for chunk in input.chunks(buffer_size) {
+            let mut left = chunk.to_vec();
+            let mut right = chunk.to_vec();
+            self.process(&mut left, &mut right);
+            output.extend_from_slice(&left);
+        }
+
+        output
+    }
+
+    /// Queues a CC for the next `process` call. `value` is normalized.
+    pub fn send_cc(&mut self, timing: u32, cc: u8, value: f32) {
+        self.context.events.push_back(NoteEvent::MidiCC {
+            timing,
+            channel: 0,
+            cc,
+            value,
+        });
+    }
+
+    pub fn latency(&self) -> u32 {
+        self.context.latency.get()
+    }
+}
+
+/// Deterministic white noise in `[-amplitude, amplitude]`.
+pub fn noise(num_samples: usize, amplitude: f32) -> Vec<f32> {
+    let mut state: u32 = 0x1234_5678;
+    (0..num_samples)
+        .map(|_| {
+            state ^= state << 13;
+            state ^= state >> 17;
+            state ^= state << 5;
+            (state as f32 / u32::MAX as f32 * 2.0 - 1.0) * amplitude
+        })
+        .collect()
+}
@@ -0,0 +1,1035 @@
+mod common;
+
+use common::{noise, TestHost, SAMPLE_RATE};
+use jdsp::OversampleFactor;
+use nih_plug::prelude::*;
+use nih_plug::wrapper::state::{ParamValue, PluginState};
+use std::collections::{BTreeMap, BTreeSet};
+use Nonlinear_ADAA::randomize::{
+    random_changes, random_values, RandomizeTarget, Rng, DRIVE_DB_RANGE, MIX_RANGE,
+    PREFILTER_HZ_RANGE,
+};
+use Nonlinear_ADAA::undo::{ParamChange, UndoHistory, MAX_STEPS};
+use Nonlinear_ADAA::{AnalysisMode, ClipStyle, DiodePreset, NonlinearAdaa};
+
+// odd sized host buffers so the plugin's internal blocks of 32 do not line up with them
+const BUFFER_SIZE: usize = 100;
+
+#[test]
+fn test_latency_reported_on_initialize() {
+    let mut host = TestHost::new();
+    let init_latency = host.latency();
+    assert!(init_latency > 0);
+
+    host.process(&mut [0.0; 64], &mut [0.0; 64]);
+
+    assert_eq!(host.latency(), init_latency);
+}
+
+#[test]
+fn test_latency_follows_oversample_factor() {
+    for (idx, name) in OversampleFactor::variants().iter().enumerate() {
+        let mut host = TestHost::new();
+        host.set_param("os level", idx as f32, true);
+        host.set_param("dry wet", 0.0, true);
+
+        let mut impulse = vec![0.0_f32; 4096];
+        impulse[0] = 1.0;
+        let output = host.process_signal(&impulse, BUFFER_SIZE);
+
+        let peak = output
+            .iter()
+            .enumerate()
+            .max_by(|a, b| a.1.abs().total_cmp(&b.1.abs()))
+            .map(|(i, _)| i)
+            .unwrap();
+
+        assert_eq!(
+            peak as u32,
+            host.latency(),
+            "dry path delay does not match reported latency at {name}"
+        );
+    }
+}
+
+#[test]
+fn test_dry_path_nulls_at_zero_mix() {
+    let mut host = TestHost::new();
+    host.set_param("dry wet", 0.0, true);
+    host.set_param("gain", util::db_to_gain(40.0), true);
+
+    let input = noise(4096, 0.5);
+    let output = host.process_signal(&input, BUFFER_SIZE);
+    let latency = host.latency() as usize;
+
+    output
+        .iter()
+        .skip(latency)
+        .zip(input.iter())
+        .enumerate()
+        .for_each(|(i, (out, dry))| {
+            assert!(
+                (out - dry).abs() < 1e-6,
+                "sample {i}: output {out} does not null against delayed input {dry}"
+            )
+        });
+}
+
+#[test]
+fn test_bypass_leaves_buffer_untouched() {
+    let mut host = TestHost::new();
+    host.set_param("plugin bypass", 1.0, true);
+    host.set_param("gain", util::db_to_gain(40.0), true);
+
+    let input = noise(1024, 0.5);
+    let output = host.process_signal(&input, BUFFER_SIZE);
+
+    assert_eq!(output, input);
+}
+
+#[test]
+fn test_gain_change_between_buffers_is_smoothed() {
+    let mut host = TestHost::new();
+    host.set_param("gain", util::db_to_gain(0.0), true);
+
+    // settle the oversampling filters on a small DC level
+    let input = vec![0.01_f32; SAMPLE_RATE as usize / 2];
+    let before = *host.process_signal(&input, BUFFER_SIZE).last().unwrap();
+
+    // automate the gain the way a host splits buffers at an automation point
+    host.set_param("gain", util::db_to_gain(40.0), false);
+    let after = host.process_signal(&input, BUFFER_SIZE);
+
+    assert!(after.iter().all(|x| x.is_finite()));
+    assert!(
+        (after[0] - before).abs() < 1e-3,
+        "gain jumped instead of smoothing: {before} -> {}",
+        after[0]
+    );
+    assert!(
+        after
+            .windows(2)
+            .all(|pair| (pair[1] - pair[0]).abs() < 1e-2),
+        "discontinuity while gain was smoothing"
+    );
+    assert!(*after.last().unwrap() > before * 2.0);
+}
+
+#[test]
+fn test_gain_change_mid_buffer_starts_at_its_offset() {
+    let offset = 37;
+    let input = vec![0.01_f32; SAMPLE_RATE as usize / 2];
+    let mut reference = TestHost::new();
+    let mut automated = TestHost::new();
+    reference.process_signal(&input, BUFFER_SIZE);
+    automated.process_signal(&input, BUFFER_SIZE);
+
+    let (mut ref_left, mut ref_right) = (vec![0.01_f32; BUFFER_SIZE], vec![0.01_f32; BUFFER_SIZE]);
+    let (mut left, mut right) = (ref_left.clone(), ref_right.clone());
+    reference.process(&mut ref_left, &mut ref_right);
+    automated.process_with_automation(
+        &mut left,
+        &mut right,
+        offset,
+        "gain",
+        util::db_to_gain(40.0),
+    );
+
+    // nothing ahead of the automation point may hear the new gain
+    assert!(
+        left[..offset]
+            .iter()
+            .zip(&ref_left[..offset])
+            .all(|(out, reference)| (out - reference).abs() < 1e-6),
+        "gain change leaked ahead of its offset"
+    );
+
+    let after = automated.process_signal(&input, BUFFER_SIZE);
+    let reference_after = reference.process_signal(&input, BUFFER_SIZE);
+    assert!(
+        left.iter()
+            .chain(&after)
+            .collect::<Vec<_>>()
+            .windows(2)
+            .all(|pair| (pair[1] - pair[0]).abs() < 1e-2),
+        "discontinuity while gain was smoothing"
+    );
+    assert!(*after.last().unwrap() > *reference_after.last().unwrap() * 2.0);
+}
+
+#[test]
+fn test_meters_rest_while_editor_is_closed() {
+    let mut host = TestHost::new();
+    host.process_signal(&noise(4096, 1.0), BUFFER_SIZE);
+
+    // the meters are only fed while the editor shows them
+    assert_eq!(host.plugin.meter_levels(), [0.0; 4]);
+}
+
+#[test]
+fn test_gate_opens_and_closes_with_hysteresis() {
+    let mut host = TestHost::new();
+    host.set_param("gate enable", 1.0, true);
+    host.set_param("gate threshold", -30.0, true);
+    host.set_param("gate hysteresis", 10.0, true);
+    host.set_param("gate attack", 0.05, true);
+    host.set_param("gate hold", 0.0, true);
+    host.set_param("gate release", 5.0, true);
+    host.set_param("gate range", 40.0, true);
+    host.set_param("dry wet", 1.0, true);
+
+    // gain of the gate at the end of a quarter second of a 1 kHz sine at `amplitude`
+    let mut settled_gain = |amplitude: f32| {
+        let sine: Vec<f32> = (0..SAMPLE_RATE as usize / 4)
+            .map(|n| {
+                amplitude * (2.0 * std::f32::consts::PI * 1000.0 * n as f32 / SAMPLE_RATE).sin()
+            })
+            .collect();
+        let output = host.process_signal(&sine, BUFFER_SIZE);
+        output[output.len() - 2048..]
+            .iter()
+            .fold(0.0_f32, |peak, x| peak.max(x.abs()))
+            / amplitude
+    };
+    let floor = util::db_to_gain(-40.0);
+
+    // -34 dB sits between the close (-40 dB) and open (-30 dB) thresholds, so the gate keeps
+    // whichever state it was in
+    let open = settled_gain(0.02);
+    assert!(
+        (open - 1.0).abs() < 0.1,
+        "gate closed above its close threshold: {open}"
+    );
+    let closed = settled_gain(0.005);
+    assert!(
+        closed > floor * 0.5 && closed < floor * 2.0,
+        "closed gate gain {closed} is not at the range floor of {floor}"
+    );
+    let still_closed = settled_gain(0.02);
+    assert!(
+        still_closed < floor * 2.0,
+        "gate reopened below its open threshold: {still_closed}"
+    );
+    let reopened = settled_gain(0.1);
+    assert!(
+        (reopened - 1.0).abs() < 0.1,
+        "gate did not open at -20 dB: {reopened}"
+    );
+}
+
+#[test]
+fn test_limiter_latency_is_reported() {
+    let mut host = TestHost::new();
+    let latency_without_ceiling = host.latency();
+    host.set_param("ceiling mode", 1.0, true);
+    host.set_param("dry wet", 0.0, true);
+
+    let mut impulse = vec![0.0_f32; 4096];
+    impulse[0] = 0.1;
+    let output = host.process_signal(&impulse, BUFFER_SIZE);
+
+    let peak = output
+        .iter()
+        .enumerate()
+        .max_by(|a, b| a.1.abs().total_cmp(&b.1.abs()))
+        .map(|(i, _)| i)
+        .unwrap();
+
+    assert!(host.latency() > latency_without_ceiling);
+    assert_eq!(peak as u32, host.latency());
+}
+
+#[test]
+fn test_limiter_holds_ceiling() {
+    let mut host = TestHost::new();
+    let ceiling = util::db_to_gain(-6.0);
+    host.set_param("ceiling mode", 1.0, true);
+    host.set_param("ceiling", ceiling, true);
+    host.set_param("dry wet", 0.0, true);
+
+    let input = noise(SAMPLE_RATE as usize, 1.0);
+    let output = host.process_signal(&input, BUFFER_SIZE);
+
+    output.iter().enumerate().for_each(|(i, x)| {
+        assert!(
+            x.abs() <= ceiling + 1e-5,
+            "sample {i}: {x} exceeds the ceiling of {ceiling}"
+        )
+    });
+}
+
+#[test]
+fn test_limiter_holds_ceiling_on_single_sample_spikes() {
+    let mut host = TestHost::new();
+    let ceiling = util::db_to_gain(-6.0);
+    host.set_param("ceiling mode", 1.0, true);
+    host.set_param("ceiling", ceiling, true);
+    // the delayed dry signal reaches the limiter untouched, so the spikes stay one sample wide
+    host.set_param("dry wet", 0.0, true);
+
+    let mut input = noise(SAMPLE_RATE as usize / 4, 0.1);
+    input
+        .iter_mut()
+        .step_by(311)
+        .enumerate()
+        .for_each(|(i, x)| *x = if i % 2 == 0 { 1.0 } else { -1.0 });
+    let output = host.process_signal(&input, BUFFER_SIZE);
+
+    output.iter().enumerate().for_each(|(i, x)| {
+        assert!(
+            x.abs() <= ceiling + 1e-6,
+            "sample {i}: {x} exceeds the ceiling of {ceiling}"
+        )
+    });
+}
+
+#[test]
+fn test_feedback_stays_bounded() {
+    for (style, amount) in [(0.0, -0.95), (0.0, 0.95), (1.0, -0.95), (1.0, 0.95)] {
+        let mut host = TestHost::new();
+        host.set_param("nl proc", style, true);
+        host.set_param("feedback", amount, true);
+        host.set_param("gain", util::db_to_gain(60.0), true);
+        host.set_param("output", util::db_to_gain(0.0), true);
+        host.initialize();
+
+        let input = noise(SAMPLE_RATE as usize, 1.0);
+        let output = host.process_signal(&input, BUFFER_SIZE);
+
+        // both curves clip at 1, so anything beyond the decimation filter's overshoot means the
+        // loop is adding energy of its own
+        assert!(
+            output.iter().all(|x| x.is_finite() && x.abs() < 1.25),
+            "feedback of {amount} with style {style} ran away"
+        );
+
+        // once the input stops the loop has to die out rather than sustain itself
+        let tail = host.process_signal(&vec![0.0; SAMPLE_RATE as usize / 2], BUFFER_SIZE);
+        let tail_peak = tail[tail.len() - 4096..]
+            .iter()
+            .fold(0.0_f32, |peak, x| peak.max(x.abs()));
+        assert!(
+            tail_peak < 1e-4,
+            "feedback of {amount} with style {style} kept ringing at {tail_peak}"
+        );
+    }
+}
+
+#[test]
+fn test_emphasis_is_flat_for_small_signals() {
+    let input: Vec<f32> = noise(8192, 0.01);
+    let render = |emphasis: f32| {
+        let mut host = TestHost::new();
+        host.set_param("emphasis", emphasis, true);
+        host.set_param("emphasis frequency", 1000.0, true);
+        host.process_signal(&input, BUFFER_SIZE)
+    };
+
+    let flat = render(0.0);
+    let emphasized = render(24.0);
+
+    flat.iter()
+        .zip(emphasized.iter())
+        .enumerate()
+        .for_each(|(i, (a, b))| {
+            assert!(
+                (a - b).abs() < 1e-4,
+                "sample {i}: emphasis changed the small signal response ({a} vs {b})"
+            )
+        });
+}
+
+#[test]
+fn test_emphasis_stays_flat_while_it_glides() {
+    let input: Vec<f32> = noise(16384, 0.01);
+    let render = |to: f32| {
+        let mut host = TestHost::new();
+        host.set_param("emphasis frequency", 1000.0, true);
+        let mut output = host.process_signal(&input[..4000], BUFFER_SIZE);
+        // glides through the smoother while the boosted samples are still in the oversampler
+        host.set_param("emphasis", to, false);
+        output.extend(host.process_signal(&input[4000..], BUFFER_SIZE));
+        output
+    };
+
+    let flat = render(0.0);
+    let automated = render(24.0);
+
+    flat.iter()
+        .zip(automated.iter())
+        .enumerate()
+        .for_each(|(i, (a, b))| {
+            assert!(
+                (a - b).abs() < 2e-4,
+                "sample {i}: the emphasis glide changed the small signal response ({a} vs {b})"
+            )
+        });
+}
+
+#[test]
+fn test_morph_endpoints_match_single_style() {
+    let input = noise(4096, 0.5);
+    let render = |style: f32, style_b: f32, morph: f32| {
+        let mut host = TestHost::new();
+        host.set_param("gain", util::db_to_gain(20.0), true);
+        host.set_param("nl proc", style, true);
+        host.set_param("nl proc b", style_b, true);
+        host.set_param("morph", morph, true);
+        // start in the chosen styles rather than crossfading into them
+        host.initialize();
+        host.process_signal(&input, BUFFER_SIZE)
+    };
+
+    let hard_clip = render(0.0, 0.0, 0.0);
+    let tanh = render(1.0, 1.0, 0.0);
+
+    assert_eq!(render(0.0, 1.0, 0.0), hard_clip);
+    render(0.0, 1.0, 1.0)
+        .iter()
+        .zip(tanh.iter())
+        .for_each(|(a, b)| assert!((a - b).abs() < 1e-6));
+}
+
+#[test]
+fn test_style_switch_is_click_free() {
+    // a full scale 100 Hz sine, switched on one of its peaks where hard clip and tanh are
+    // furthest apart and the signal itself barely moves
+    let period = SAMPLE_RATE / 100.0;
+    let sine: Vec<f32> = (0..SAMPLE_RATE as usize / 4)
+        .map(|n| (2.0 * std::f32::consts::PI * n as f32 / period).sin())
+        .collect();
+    let switch_at = (25.25 * period).round() as usize;
+    let (first_part, second_part) = sine.split_at(switch_at);
+
+    // largest step between neighbouring samples within a few milliseconds of the switch
+    let max_step_around = |output: &[f32], at: usize| {
+        output[at - 40..at + 40]
+            .windows(2)
+            .map(|w| (w[1] - w[0]).abs())
+            .fold(0.0_f32, f32::max)
+    };
+    let render = |id: &str, from: f32, to: f32, switch: bool| {
+        let mut host = TestHost::new();
+        host.set_param(id, from, true);
+        host.initialize();
+
+        let mut output = host.process_signal(first_part, BUFFER_SIZE);
+        if switch {
+            host.set_param(id, to, true);
+        }
+        output.extend(host.process_signal(second_part, BUFFER_SIZE));
+        (output, host.latency() as usize)
+    };
+
+    for (id, from, to) in [("nl proc", 0.0, 1.0), ("ad level", 0.0, 1.0)] {
+        let (output, latency) = render(id, from, to, true);
+        let at = switch_at + latency;
+        let max_step = max_step_around(&output, at);
+
+        assert!(
+            max_step < 0.02,
+            "switching {id} jumped by {max_step} between samples"
+        );
+
+        // without the crossfade the output would cut straight from one curve to the other
+        let (before, _) = render(id, from, to, false);
+        let (after, _) = render(id, to, to, false);
+        let hard_cut: Vec<f32> = before[..at].iter().chain(&after[at..]).copied().collect();
+        let hard_cut_step = max_step_around(&hard_cut, at);
+
+        if id == "nl proc" {
+            assert!(
+                hard_cut_step > 0.1,
+                "hard clip and tanh should differ at the peak, cut jumped by {hard_cut_step}"
+            );
+        }
+        assert!(
+            max_step <= hard_cut_step.max(0.01),
+            "switching {id} stepped by {max_step}, more than an uncrossfaded cut ({hard_cut_step})"
+        );
+    }
+}
+
+#[test]
+fn test_style_switch_mid_fade_is_click_free() {
+    let period = SAMPLE_RATE / 100.0;
+    let sine: Vec<f32> = (0..SAMPLE_RATE as usize / 4)
+        .map(|n| (2.0 * std::f32::consts::PI * n as f32 / period).sin())
+        .collect();
+    // hard clip to tanh on a peak, then to the diode clipper 2 ms into that fade
+    let first_switch = (25.25 * period).round() as usize;
+    let second_switch = first_switch + (0.002 * SAMPLE_RATE) as usize;
+
+    let mut host = TestHost::new();
+    host.set_param("nl proc", 0.0, true);
+    host.initialize();
+    let mut output = host.process_signal(&sine[..first_switch], BUFFER_SIZE);
+    host.set_param("nl proc", 1.0, true);
+    output.extend(host.process_signal(&sine[first_switch..second_switch], BUFFER_SIZE));
+    host.set_param("nl proc", 3.0, true);
+    output.extend(host.process_signal(&sine[second_switch..], BUFFER_SIZE));
+
+    let latency = host.latency() as usize;
+    for at in [first_switch + latency, second_switch + latency] {
+        let max_step = output[at - 40..at + 40]
+            .windows(2)
+            .map(|w| (w[1] - w[0]).abs())
+            .fold(0.0_f32, f32::max);
+        assert!(
+            max_step < 0.02,
+            "switching style at {at} jumped by {max_step} between samples"
+        );
+    }
+}
+
+#[test]
+fn test_hysteresis_is_stable() {
+    for (drive, saturation, width) in [(0.0, 0.0, 0.0), (0.5, 0.5, 0.5), (1.0, 1.0, 1.0)] {
+        for (os_idx, _) in OversampleFactor::variants().iter().enumerate() {
+            let mut host = TestHost::new();
+            host.set_param("nl proc", 2.0, true);
+            host.set_param("hyst drive", drive, true);
+            host.set_param("hyst saturation", saturation, true);
+            host.set_param("hyst width", width, true);
+            host.set_param("os level", os_idx as f32, true);
+            host.set_param("gain", util::db_to_gain(40.0), true);
+            host.initialize();
+
+            // hot noise, then a sustained hot 100 Hz sine that drives the loop round and round
+            let period = SAMPLE_RATE as usize / 100;
+            let mut input = noise(8192, 1.0);
+            input.extend(
+                (0..SAMPLE_RATE as usize / 2)
+                    .map(|n| (2.0 * std::f32::consts::PI * n as f32 / period as f32).sin()),
+            );
+            let output = host.process_signal(&input, BUFFER_SIZE);
+
+            // the magnetisation is normalised to +-1, anything past the decimation filter's
+            // overshoot means the model left its range
+            assert!(
+                output.iter().all(|x| x.is_finite() && x.abs() <= 1.25),
+                "hysteresis left its range at drive {drive}, saturation {saturation}, width {width}"
+            );
+
+            // a symmetric input traces a symmetric loop, so whole periods average out to zero
+            let tail = &output[output.len() - 10 * period..];
+            let dc = tail.iter().sum::<f32>() / tail.len() as f32;
+            assert!(
+                dc.abs() < 0.05,
+                "hysteresis drifted to {dc} DC at drive {drive}, saturation {saturation}, width {width}"
+            );
+        }
+    }
+}
+
+#[test]
+fn test_diode_clipper_is_stable() {
+    let gain = util::db_to_gain(60.0);
+
+    for (preset_idx, preset_name) in DiodePreset::variants().iter().enumerate() {
+        // noise peaks at 1 V before the gain, with headroom for the upsampling filter's
+        // overshoot, the trapezoidal rule and the decimation filter
+        let limit = DiodePreset::from_index(preset_idx).output_limit(1.25 * gain) * 1.25;
+
+        for (os_idx, _) in OversampleFactor::variants().iter().enumerate() {
+            let mut host = TestHost::new();
+            host.set_param("nl proc", 3.0, true);
+            host.set_param("diode preset", preset_idx as f32, true);
+            host.set_param("os level", os_idx as f32, true);
+            host.set_param("gain", gain, true);
+            host.initialize();
+
+            let output = host.process_signal(&noise(8192, 1.0), BUFFER_SIZE);
+
+            assert!(
+                output.iter().all(|x| x.is_finite() && x.abs() <= limit),
+                "diode clipper preset {preset_name} went past its limit of {limit}"
+            );
+        }
+    }
+}
+
+#[test]
+fn test_chebyshev_without_harmonics_is_clean() {
+    let sine: Vec<f32> = (0..8192)
+        .map(|n| 0.5 * (2.0 * std::f32::consts::PI * 441.0 * n as f32 / SAMPLE_RATE).sin())
+        .collect();
+
+    for order in [0.0, 1.0] {
+        let render = |style: f32| {
+            let mut host = TestHost::new();
+            host.set_param("nl proc", style, true);
+            host.set_param("ad level", order, true);
+            host.initialize();
+            host.process_signal(&sine, BUFFER_SIZE)
+        };
+
+        // with every harmonic at zero the shaper is the identity below full scale, like the hard
+        // clip
+        render(4.0)
+            .iter()
+            .zip(render(0.0).iter())
+            .for_each(|(a, b)| assert!((a - b).abs() < 1e-4));
+    }
+}
+
+#[test]
+fn test_chebyshev_harmonics_are_stable() {
+    let mut host = TestHost::new();
+    host.set_param("nl proc", 4.0, true);
+    let mut weight_sum = 0.0;
+    let mut dc_offset = 0.0_f32;
+    for harmonic in 2..=8 {
+        let invert = harmonic % 2 == 1;
+        host.set_param(&format!("harmonic {harmonic}_level"), 1.0, true);
+        host.set_param(
+            &format!("harmonic {harmonic}_invert"),
+            invert as u8 as f32,
+            true,
+        );
+
+        let weight = if invert { -1.0 } else { 1.0 };
+        weight_sum += 1.0;
+        // T_k(0) is +-1 for even k and 0 for odd k, the shaper subtracts that DC again
+        if harmonic % 2 == 0 {
+            dc_offset += weight * if harmonic % 4 == 0 { 1.0 } else { -1.0 };
+        }
+    }
+    host.set_param("gain", util::db_to_gain(20.0), true);
+    host.initialize();
+
+    let output = host.process_signal(&noise(8192, 1.0), BUFFER_SIZE);
+
+    // every |T_k| <= 1 on the shaper's range, so the fundamental, the weighted harmonics and the
+    // removed DC add up to at most this, plus the decimation filter's overshoot
+    let limit = (1.0 + weight_sum + dc_offset.abs()) * 1.25;
+    output.iter().enumerate().for_each(|(i, x)| {
+        assert!(
+            x.is_finite() && x.abs() <= limit,
+            "sample {i}: {x} is past the limit of {limit} set by the harmonic weights"
+        )
+    });
+}
+
+#[test]
+fn test_analysis_mode_is_not_saved() {
+    let host = TestHost::new();
+    host.plugin.params().analysis_mode.set(AnalysisMode::Inject);
+
+    let params = host.plugin.params();
+    assert!(!params
+        .param_map()
+        .iter()
+        .any(|(id, _, _)| id.contains("analysis mode")));
+    assert!(!params
+        .serialize_fields()
+        .keys()
+        .any(|key| key.contains("analysis")));
+    assert_eq!(
+        TestHost::new().plugin.params().analysis_mode.get(),
+        AnalysisMode::Off
+    );
+}
+
+#[test]
+fn test_analysis_tone_needs_the_editor_open() {
+    let input = noise(8192, 0.5);
+    let [off, inject] = [AnalysisMode::Off, AnalysisMode::Inject].map(|mode| {
+        let mut host = TestHost::new();
+        host.plugin.params().analysis_mode.set(mode);
+        host.set_param("analysis level", -6.0, true);
+        host.initialize();
+        host.process_signal(&input, BUFFER_SIZE)
+    });
+
+    // with no editor open the input is processed as if the analysis were off
+    assert_eq!(off, inject);
+}
+
+fn param_ptr(host: &TestHost, id: &str) -> ParamPtr {
+    host.plugin
+        .params()
+        .param_map()
+        .into_iter()
+        .find(|(param_id, _, _)| param_id == id)
+        .map(|(_, param, _)| param)
+        .unwrap()
+}
+
+fn change(param: ParamPtr, before: f32, after: f32) -> ParamChange {
+    ParamChange {
+        param,
+        before,
+        after,
+    }
+}
+
+#[test]
+fn test_undo_steps_through_whole_gestures() {
+    let host = TestHost::new();
+    let gain = param_ptr(&host, "gain");
+    let mix = param_ptr(&host, "dry wet");
+    let mut history = UndoHistory::default();
+
+    // two overlapping gestures, each undone as a whole
+    history.begin(gain, 0.2);
+    history.begin(mix, 1.0);
+    history.set(gain, 0.3);
+    history.set(mix, 0.6);
+    history.set(gain, 0.5);
+    history.end(gain);
+    history.set(mix, 0.4);
+    history.end(mix);
+
+    assert_eq!(history.undo(), Some(vec![(mix, 1.0)]));
+    assert_eq!(history.undo(), Some(vec![(gain, 0.2)]));
+    assert_eq!(history.undo(), None);
+    assert_eq!(history.redo(), Some(vec![(gain, 0.5)]));
+    assert_eq!(history.redo(), Some(vec![(mix, 0.4)]));
+    assert_eq!(history.redo(), None);
+}
+
+#[test]
+fn test_undo_drops_gestures_that_change_nothing() {
+    let host = TestHost::new();
+    let gain = param_ptr(&host, "gain");
+    let mut history = UndoHistory::default();
+
+    history.begin(gain, 0.2);
+    history.set(gain, 0.4);
+    history.set(gain, 0.2);
+    history.end(gain);
+    // an end without a begin is ignored
+    history.end(gain);
+    history.record(vec![change(gain, 0.7, 0.7)]);
+
+    assert!(!history.can_undo());
+}
+
+#[test]
+fn test_undo_keeps_the_latest_steps() {
+    let host = TestHost::new();
+    let gain = param_ptr(&host, "gain");
+    let mut history = UndoHistory::default();
+
+    let num_steps = MAX_STEPS + 10;
+    let value = |step: usize| step as f32 / num_steps as f32;
+    for step in 0..num_steps {
+        history.record(vec![change(gain, value(step), value(step + 1))]);
+    }
+
+    let undone: Vec<_> = std::iter::from_fn(|| history.undo()).collect();
+    assert_eq!(undone.len(), MAX_STEPS);
+    assert_eq!(undone.last(), Some(&vec![(gain, value(10))]));
+}
+
+#[test]
+fn test_new_edit_clears_redo() {
+    let host = TestHost::new();
+    let gain = param_ptr(&host, "gain");
+    let mut history = UndoHistory::default();
+
+    history.record(vec![change(gain, 0.2, 0.5)]);
+    history.undo();
+    assert!(history.can_redo());
+
+    history.record(vec![change(gain, 0.2, 0.3)]);
+    assert!(!history.can_redo());
+    assert_eq!(history.undo(), Some(vec![(gain, 0.2)]));
+}
+
+fn map_cc(host: &TestHost, cc: u8, id: &str) {
+    host.plugin.params().deserialize_fields(&BTreeMap::from([(
+        String::from("midi-cc-map"),
+        format!("{{\"{cc}\":\"{id}\"}}"),
+    )]));
+}
+
+#[test]
+fn test_midi_cc_drives_mapped_parameter() {
+    let input = noise(8192, 0.5);
+
+    let mut mapped = TestHost::new();
+    map_cc(&mapped, 20, "dry wet");
+    mapped.send_cc(10, 20, 0.0);
+    let mapped_output = mapped.process_signal(&input, BUFFER_SIZE);
+
+    let mut unmapped = TestHost::new();
+    unmapped.send_cc(10, 20, 0.0);
+    let unmapped_output = unmapped.process_signal(&input, BUFFER_SIZE);
+
+    // once the mix has glided to fully dry the output is the delayed input
+    let latency = mapped.latency() as usize;
+    let settled = 4096;
+    assert!(mapped_output[settled..]
+        .iter()
+        .zip(&input[settled - latency..])
+        .all(|(out, dry)| (out - dry).abs() < 1e-6));
+    assert!(unmapped_output[settled..]
+        .iter()
+        .zip(&input[settled - latency..])
+        .any(|(out, dry)| (out - dry).abs() > 1e-3));
+}
+
+#[test]
+fn test_midi_cc_switches_enum_with_editor_closed() {
+    let input: Vec<f32> = noise(8192, 0.5);
+    let render = |cc_style: Option<f32>, style: f32| {
+        let mut host = TestHost::new();
+        host.set_param("gain", util::db_to_gain(20.0), true);
+        host.set_param("nl proc", style, true);
+        host.initialize();
+        if let Some(value) = cc_style {
+            map_cc(&host, 20, "nl proc");
+            host.send_cc(0, 20, value);
+        }
+        let output = host.process_signal(&input, BUFFER_SIZE);
+
+        // no editor passed the CC on to the host
+        assert_eq!(
+            host.plugin.params().nonlinearity.nl_proc_type.value() as usize,
+            style as usize
+        );
+        output
+    };
+
+    // a quarter of the way along the five styles is tanh
+    let switched = render(Some(0.25), 0.0);
+    let tanh = render(None, 1.0);
+    let hard_clip = render(None, 0.0);
+
+    // once the crossfade into tanh is over the output matches a plain tanh
+    let settled = 4096;
+    assert!(switched[settled..]
+        .iter()
+        .zip(&tanh[settled..])
+        .all(|(a, b)| (a - b).abs() < 1e-6));
+    assert!(switched[settled..]
+        .iter()
+        .zip(&hard_clip[settled..])
+        .any(|(a, b)| (a - b).abs() > 1e-3));
+}
+
+#[test]
+fn test_midi_cc_switches_bypass_on_its_sample() {
+    let input = noise(1024, 0.5);
+    let switch_at = 600;
+
+    let mut host = TestHost::new();
+    host.set_param("gain", util::db_to_gain(20.0), true);
+    host.initialize();
+    map_cc(&host, 20, "plugin bypass");
+    host.send_cc(switch_at as u32, 20, 1.0);
+
+    let mut left = input.clone();
+    let mut right = input.clone();
+    host.process(&mut left, &mut right);
+
+    // processed up to the CC, passed through untouched from its sample on
+    assert!(left[..switch_at]
+        .iter()
+        .zip(&input)
+        .any(|(out, dry)| (out - dry).abs() > 1e-3));
+    assert_eq!(left[switch_at..], input[switch_at..]);
+}
+
+#[test]
+fn test_midi_cc_map_is_saved_with_state() {
+    let host = TestHost::new();
+    map_cc(&host, 20, "dry wet");
+    map_cc(&host, 21, "no such parameter");
+
+    let saved = host.plugin.params().serialize_fields();
+    assert_eq!(saved["midi-cc-map"], "{}");
+
+    map_cc(&host, 20, "dry wet");
+    let saved = host.plugin.params().serialize_fields();
+
+    let restored = TestHost::new();
+    restored.plugin.params().deserialize_fields(&saved);
+    assert_eq!(
+        restored.plugin.params().serialize_fields()["midi-cc-map"],
+        "{\"20\":\"dry wet\"}"
+    );
+}
+
+#[test]
+fn test_parameter_groups_keep_ids() {
+    let host = TestHost::new();
+    let groups: BTreeMap<String, String> = host
+        .plugin
+        .params()
+        .param_map()
+        .into_iter()
+        .map(|(id, _, group)| (id, group))
+        .collect();
+
+    // saved sessions refer to parameters by these ids
+    for (id, group) in [
+        ("gain", "Input"),
+        ("gate threshold", "Input"),
+        ("nl proc", "Nonlinearity"),
+        ("ad level", "Nonlinearity"),
+        ("os level", "Nonlinearity"),
+        ("harmonic 2_level", "Nonlinearity/Harmonic 2"),
+        ("stage 2_drive", "Nonlinearity/Stage 2"),
+        ("pre filter cutoff", "Filter"),
+        ("output", "Output"),
+        ("dry wet", "Output"),
+        ("plugin bypass", ""),
+    ] {
+        assert_eq!(groups.get(id).map(String::as_str), Some(group), "{id}");
+    }
+}
+
+/// Reads a state blob saved by an earlier version and runs it through the plugin's migration.
+fn load_old_state(name: &str) -> PluginState {
+    let json = std::fs::read_to_string(format!("./tests/state_data/{name}")).unwrap();
+    let mut state: PluginState = serde_json::from_str(&json).unwrap();
+    NonlinearAdaa::filter_state(&mut state);
+    state
+}
+
+fn enum_id<'a>(state: &'a PluginState, id: &str) -> Option<&'a str> {
+    match state.params.get(id) {
+        Some(ParamValue::String(variant)) => Some(variant),
+        _ => None,
+    }
+}
+
+#[test]
+fn test_state_from_before_versioning_gets_clip_style_ids() {
+    let state = load_old_state("v0_baseline.json");
+
+    assert_eq!(enum_id(&state, "nl proc"), Some("tanh"));
+    assert_eq!(state.fields["state-version"], "1");
+    // the remaining parameters load as they are
+    assert!(matches!(state.params["dry wet"], ParamValue::F32(mix) if mix == 0.75));
+    assert!(!matches!(state.params["ad level"], ParamValue::String(_)));
+}
+
+#[test]
+fn test_state_with_indexed_stage_styles_migrates() {
+    let state = load_old_state("v0_stages.json");
+
+    assert_eq!(enum_id(&state, "nl proc"), Some("hard clip"));
+    assert_eq!(enum_id(&state, "nl proc b"), Some("tanh"));
+    assert_eq!(enum_id(&state, "stage 2_style"), Some("tanh"));
+    assert_eq!(enum_id(&state, "stage 3_style"), Some("hard clip"));
+    // an index no version ever saved falls back to the default
+    assert!(!state.params.contains_key("stage 4_style"));
+}
+
+#[test]
+fn test_state_with_clip_style_ids_is_kept() {
+    let state = load_old_state("v0_clip_style_ids.json");
+
+    assert_eq!(enum_id(&state, "nl proc"), Some("hysteresis"));
+    assert_eq!(enum_id(&state, "nl proc b"), Some("diode clipper"));
+    assert_eq!(enum_id(&state, "stage 2_style"), Some("chebyshev"));
+
+    let host = TestHost::new();
+    host.plugin.params().deserialize_fields(&state.fields);
+    let fields = host.plugin.params().serialize_fields();
+    assert_eq!(fields["editor-theme"], "\"Light\"");
+    assert_eq!(fields["state-version"], "1");
+}
+
+#[test]
+fn test_current_state_is_not_migrated() {
+    let host = TestHost::new();
+    let mut state = PluginState {
+        version: String::from("0.1.0"),
+        params: BTreeMap::from([(String::from("nl proc"), ParamValue::I32(1))]),
+        fields: host.plugin.params().serialize_fields(),
+    };
+    assert_eq!(state.fields["state-version"], "1");
+
+    // only state from before clip styles had ids stores them as an index
+    NonlinearAdaa::filter_state(&mut state);
+    assert!(matches!(state.params["nl proc"], ParamValue::I32(1)));
+}
+
+#[test]
+fn test_randomize_stays_in_musical_ranges() {
+    let mut rng = Rng::new(0x2545_f491);
+    for _ in 0..10_000 {
+        let drive = RandomizeTarget::Drive.random_plain(&mut rng);
+        assert!(DRIVE_DB_RANGE.contains(&drive));
+        let cutoff = RandomizeTarget::Prefilter.random_plain(&mut rng);
+        assert!(
+            (PREFILTER_HZ_RANGE.start * 0.999..PREFILTER_HZ_RANGE.end * 1.001).contains(&cutoff)
+        );
+        let mix = RandomizeTarget::Mix.random_plain(&mut rng);
+        assert!(MIX_RANGE.contains(&mix));
+    }
+}
+
+#[test]
+fn test_randomize_covers_every_style() {
+    let mut rng = Rng::new(7);
+    let styles: BTreeSet<u32> = (0..1000)
+        .map(|_| RandomizeTarget::Style.random_plain(&mut rng) as u32)
+        .collect();
+
+    assert_eq!(styles, (0..ClipStyle::variants().len() as u32).collect());
+}
+
+#[test]
+fn test_randomize_keeps_locked_targets() {
+    let host = TestHost::new();
+    let locks = BTreeSet::from([RandomizeTarget::Style, RandomizeTarget::Mix]);
+    let mut rng = Rng::new(11);
+    let values: Vec<(ParamPtr, f32)> =
+        random_values(&host.plugin.params(), &locks, &mut rng).collect();
+
+    let unlocked = ["ad level", "gain", "pre filter cutoff"].map(|id| param_ptr(&host, id));
+    assert_eq!(
+        values.iter().map(|(param, _)| *param).collect::<Vec<_>>(),
+        unlocked
+    );
+    assert!(values.iter().all(|(_, value)| (0.0..=1.0).contains(value)));
+}
+
+#[test]
+fn test_randomize_is_one_undo_step() {
+    let host = TestHost::new();
+    let changes = random_changes(&host.plugin.params(), &BTreeSet::new(), &mut Rng::new(3));
+    assert_eq!(changes.len(), RandomizeTarget::ALL.len());
+
+    let mut history = UndoHistory::default();
+    history.record(changes.clone());
+
+    // everything the randomizer moved goes back in one undo
+    let moved: Vec<(ParamPtr, f32)> = changes
+        .iter()
+        .rev()
+        .filter(|change| change.before != change.after)
+        .map(|change| (change.param, change.before))
+        .collect();
+    assert_eq!(history.undo(), Some(moved));
+    assert!(!history.can_undo());
+}
+
+#[test]
+fn test_randomize_trigger_works_with_editor_closed() {
+    let mut host = TestHost::new();
+    host.initialize();
+    *host.plugin.params().randomize_locks.write().unwrap() = RandomizeTarget::ALL
+        .into_iter()
+        .filter(|target| *target != RandomizeTarget::Mix)
+        .collect();
+
+    host.set_param("randomize", 1.0, true);
+    host.process_signal(&noise(BUFFER_SIZE, 0.5), BUFFER_SIZE);
+
+    // the mix moved into its range on the audio thread, the host's value waits for the editor
+    let params = host.plugin.params();
+    let mix = params.midi_map.value(&params.output.dry_wet);
+    assert!(MIX_RANGE.contains(&mix));
+    assert_eq!(params.output.dry_wet.value(), 1.0);
+    assert_eq!(
+        params.input.gain.value(),
+        params.midi_map.value(&params.input.gain)
+    );
+}